@@ -2,8 +2,9 @@ use image::imageops::FilterType;
 use image::RgbaImage;
 use rand::Rng;
 
-use crate::label::{Bounds, Label};
-use crate::render::RenderedScenery;
+use crate::label::{Bounds, BoundsMode, Label, SpriteBounds};
+use crate::render::instance::{compute_visibility, InstanceMap, paint_sprite};
+use crate::render::{cache, RenderedScenery};
 use crate::scenery::Scenery;
 
 pub struct Asset {
@@ -11,15 +12,21 @@ pub struct Asset {
     pub class: usize,
 }
 
-pub fn render(img: RgbaImage, assets: &[&Asset]) -> Result<RenderedScenery, String> {
-    let mut rng = rand::thread_rng();
-
+pub fn render(
+    img: RgbaImage,
+    assets: &[&Asset],
+    bounds_mode: BoundsMode,
+    rng: &mut impl Rng,
+) -> Result<RenderedScenery, String> {
     let mut buff = img;
 
+    let mut instances = InstanceMap::new(buff.width(), buff.height());
     let mut labels = Vec::new();
+    let mut sprite_pixels = Vec::new();
 
     for asset in assets {
-        let asset_img = image::open(&asset.path).unwrap();
+        // the sizes are random, only the original sprites are cached
+        let asset_img = cache::image(&asset.path);
 
         let x = rng.gen_range(0f32..buff.width() as f32 - asset_img.width() as f32) as i64;
         let y = rng.gen_range(0f32..buff.height() as f32 - asset_img.height() as f32) as i64;
@@ -27,7 +34,7 @@ pub fn render(img: RgbaImage, assets: &[&Asset]) -> Result<RenderedScenery, Stri
         let size_ratio = rng.gen_range(0.6..1.0);
 
         let resized_image = image::imageops::resize(
-            &asset_img,
+            asset_img.as_ref(),
             (asset_img.width() as f32 * size_ratio) as u32,
             (asset_img.height() as f32 * size_ratio) as u32,
             FilterType::Nearest,
@@ -35,25 +42,42 @@ pub fn render(img: RgbaImage, assets: &[&Asset]) -> Result<RenderedScenery, Stri
 
         image::imageops::overlay(&mut buff, &resized_image, x, y);
 
+        let instance = labels.len() as u16 + 1;
+        let painted = paint_sprite(&mut instances, &resized_image, x, y, instance);
+
         let x_center = x + resized_image.width() as i64 / 2;
         let y_center = y + resized_image.height() as i64 / 2;
 
-        let bounds = Bounds {
-            x_center: x_center as f32 / buff.width() as f32,
-            y_center: y_center as f32 / buff.height() as f32,
-            height: resized_image.height() as f32 / buff.height() as f32,
-            width: resized_image.width() as f32 / buff.width() as f32,
+        let sprite_bounds = SpriteBounds {
+            sprite: Bounds {
+                x_center: x_center as f32 / buff.width() as f32,
+                y_center: y_center as f32 / buff.height() as f32,
+                height: resized_image.height() as f32 / buff.height() as f32,
+                width: resized_image.width() as f32 / buff.width() as f32,
+            },
+            opaque: painted.extent.map(|extent| Bounds::from_extent(extent, buff.width(), buff.height())),
+            opaque_pixels: painted.opaque_pixels,
+        };
+
+        let Some(bounds) = sprite_bounds.bounds(bounds_mode) else {
+            continue;
         };
 
         labels.push(Label {
             bounds,
             class: asset.class,
-        })
+            instance,
+            visibility: 1.0,
+        });
+        sprite_pixels.push(sprite_bounds.opaque_pixels);
     }
 
+    compute_visibility(&mut labels, &sprite_pixels, &instances);
+
     Ok(RenderedScenery {
         image: buff,
         labels,
+        instances,
     })
 }
 
@@ -1,18 +1,51 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
 
+use serde::{Deserialize, Serialize};
+
+use crate::buidling::{Building, BuildingCharacteristics, BuildingType};
+use crate::layout::{read_file, LayoutResult};
 use crate::pathfinding::find_route_to_next_building;
 use crate::position::Pos;
 use crate::scenery::Scenery;
-use crate::troop::Troop;
-use crate::village::{ComponentId, Village};
+use crate::troop::{InvalidTroopLevel, Troop};
+use crate::village::{Component, ComponentId, ComponentType, Village};
+
+/// defenses choose their targets again at least this often, in seconds,
+/// so that they notice the troops walking into their range
+const DEFENSE_RETARGET_INTERVAL: f32 = 0.5;
 
-#[derive(Default)]
+/// Troops dropped at the start of an attack, all with a valid level
+#[derive(Default, Serialize)]
 pub struct AttackPlan {
-    pub initial_placements: Vec<Troop>,
+    initial_placements: Vec<Troop>,
+}
+
+/// an attack plan as written in its file, the levels of its troops are not checked yet
+#[derive(Deserialize)]
+struct AttackPlanFile {
+    initial_placements: Vec<Troop>,
+}
+
+impl AttackPlan {
+    pub fn new(initial_placements: Vec<Troop>) -> Result<AttackPlan, InvalidTroopLevel> {
+        for troop in &initial_placements {
+            troop.check_level()?;
+        }
+
+        Ok(AttackPlan { initial_placements })
+    }
+
+    /// loads a json or ron attack plan
+    pub fn load(path: impl AsRef<Path>) -> LayoutResult<AttackPlan> {
+        let plan: AttackPlanFile = read_file(path)?;
+
+        Ok(AttackPlan::new(plan.initial_placements)?)
+    }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct TroopId(usize);
 
 struct Action {
@@ -37,13 +70,48 @@ struct MoveToBuildingAction {
 pub struct AttackSimulationResult {
     pub village: Village,
     pub troops: Vec<Troop>,
+    /// outcome of each troop, in the order of the attack plan
+    pub troops_outcomes: Vec<TroopOutcome>,
     pub evolution_logs: Vec<EvolutionLog>,
+    /// percentage of the buildings destroyed, walls and traps excluded as in the game,
+    /// as well as the buildings that cannot be damaged
+    pub destruction: f32,
+    /// one star for 50% destruction, one for the town hall and one for 100% destruction
+    pub stars: u8,
+    /// destruction percentage at the end of each evolution, with the seconds since the start of the attack
+    pub destruction_over_time: Vec<(f32, f32)>,
+}
+
+pub struct TroopOutcome {
+    pub life_points: f32,
+    /// seconds since the start of the attack, None if the troop survived
+    pub death_time: Option<f32>,
+    /// damages dealt to the buildings and walls
+    pub damage_dealt: f32,
+}
+
+impl TroopOutcome {
+    fn is_alive(&self) -> bool {
+        self.life_points > 0.0
+    }
 }
 
 pub struct EvolutionLog {
     pub evolution_end_time: f32,
-    pub troops_paths: HashMap<TroopId, Vec<Pos>>,
+    /// sorted by troop, so that the renders of the logs do not depend on the iteration order
+    pub troops_paths: BTreeMap<TroopId, Vec<Pos>>,
     pub buildings_destroyed: Vec<ComponentId>,
+    pub troops_killed: Vec<TroopId>,
+}
+
+/// A defense that can still fire.
+/// Its hits are spread over time: the target loses `damage_per_seconds` continuously instead of
+/// losing a whole hit at each shot, so a troop may die up to one hit interval later than in the game
+struct Defense {
+    id: ComponentId,
+    center: Pos,
+    range: f32,
+    damage_per_seconds: f32,
 }
 
 pub fn simulate_attack(
@@ -51,13 +119,29 @@ pub fn simulate_attack(
     village: &Village,
     attack_plan: &AttackPlan,
 ) -> AttackSimulationResult {
+    let village_before_attack = village;
     let mut village = village.clone();
 
+    let scored_buildings = village
+        .iter_components()
+        .filter(|(_, _, component)| counts_in_destruction(component))
+        .map(|(id, _, _)| id)
+        .collect::<HashSet<_>>();
+
     let mut troops = attack_plan.initial_placements.clone();
+    let mut troops_outcomes = troops
+        .iter()
+        .map(|troop| TroopOutcome {
+            life_points: troop.hit_points(),
+            death_time: None,
+            damage_dealt: 0.0,
+        })
+        .collect::<Vec<_>>();
 
     let mut actions = Vec::new();
+    let mut defenses_targets = HashMap::new();
 
-    compute_troops_actions(&village, &troops, &mut actions, village.scenery());
+    compute_troops_actions(&village, &troops, &troops_outcomes, &mut actions, village.scenery());
 
     let mut remaining_time = simulation_time_seconds as f32;
 
@@ -66,15 +150,22 @@ pub fn simulate_attack(
     while remaining_time > 0.0 {
         let buildings_dps = compute_buildings_dps(&actions, &troops);
 
+        let defenses = live_defenses(&village);
+        update_defenses_targets(&defenses, &troops, &troops_outcomes, &mut defenses_targets);
+        let troops_dps = compute_troops_dps(&defenses, &defenses_targets);
+
         let evolution_time =
             get_shortest_action_completion_time(&actions, &troops, &buildings_dps, &village)
+                .min(get_shortest_troop_death_time(&troops_dps, &troops_outcomes))
+                .min(DEFENSE_RETARGET_INTERVAL)
                 .min(remaining_time);
 
         if evolution_time != 0.0 {
             let mut evolution_logs = EvolutionLog {
                 evolution_end_time: remaining_time - evolution_time,
-                troops_paths: HashMap::default(),
+                troops_paths: BTreeMap::default(),
                 buildings_destroyed: Vec::default(),
+                troops_killed: Vec::default(),
             };
 
             move_troops(
@@ -84,10 +175,37 @@ pub fn simulate_attack(
                 evolution_time,
             );
 
-            for (building_id, dps) in buildings_dps {
-                let destroyed = village.damage_component(dps * evolution_time, building_id);
+            let mut buildings_damages = HashMap::new();
+            for (building_id, dps) in &buildings_dps {
+                let life_points = village.get_component_lifepoints(*building_id).unwrap_or(0.0);
+                let destroyed = village.damage_component(dps * evolution_time, *building_id);
                 if destroyed {
-                    evolution_logs.buildings_destroyed.push(building_id)
+                    evolution_logs.buildings_destroyed.push(*building_id)
+                }
+
+                let remaining_life_points = village.get_component_lifepoints(*building_id).unwrap_or(0.0);
+                buildings_damages.insert(*building_id, life_points - remaining_life_points);
+            }
+
+            // the damages dealt to a building are shared between its attackers according to their dps
+            for action in &actions {
+                if let ActionKind::Attack(attack_action) = &action.kind {
+                    let troop_dps = troops[action.troop_id.0].tpe.damage_per_seconds();
+                    let share = troop_dps / buildings_dps[&attack_action.target];
+                    troops_outcomes[action.troop_id.0].damage_dealt += buildings_damages[&attack_action.target] * share;
+                }
+            }
+
+            let elapsed_time = simulation_time_seconds as f32 - remaining_time + evolution_time;
+            for (troop_id, dps) in troops_dps {
+                let outcome = &mut troops_outcomes[troop_id.0];
+                outcome.life_points = (outcome.life_points - dps * evolution_time).max(0.0);
+
+                if !outcome.is_alive() {
+                    outcome.death_time = Some(elapsed_time);
+                    // dead troops do not act anymore
+                    actions.retain(|action| action.troop_id != troop_id);
+                    evolution_logs.troops_killed.push(troop_id);
                 }
             }
 
@@ -95,15 +213,54 @@ pub fn simulate_attack(
             all_evolution_logs.push(evolution_logs);
         }
 
-        compute_troops_actions(&village, &troops, &mut actions, village.scenery());
+        compute_troops_actions(&village, &troops, &troops_outcomes, &mut actions, village.scenery());
     }
 
     assert_eq!(remaining_time, 0.0);
 
+    let destroyed_buildings = |village: &Village| {
+        scored_buildings
+            .iter()
+            .filter(|id| village.is_component_destroyed(**id))
+            .count()
+    };
+    let destruction = destruction_percentage(destroyed_buildings(&village), scored_buildings.len());
+
+    // the buildings destroyed before the attack are counted from the start
+    let mut destroyed = destroyed_buildings(village_before_attack);
+    let destruction_over_time = all_evolution_logs
+        .iter()
+        .map(|log| {
+            destroyed += log
+                .buildings_destroyed
+                .iter()
+                .filter(|id| scored_buildings.contains(id))
+                .count();
+            let time = simulation_time_seconds as f32 - log.evolution_end_time;
+            (time, destruction_percentage(destroyed, scored_buildings.len()))
+        })
+        .collect();
+
+    let town_hall_destroyed = village.iter_components().any(|(id, _, component)| {
+        matches!(
+            &component.kind,
+            ComponentType::Building(Building {
+                building_type: BuildingType::TownHall,
+                ..
+            })
+        ) && village.is_component_destroyed(id)
+    });
+
+    let stars = (destruction >= 50.0) as u8 + town_hall_destroyed as u8 + (destruction >= 100.0) as u8;
+
     AttackSimulationResult {
         village,
         troops,
+        troops_outcomes,
         evolution_logs: all_evolution_logs,
+        destruction,
+        stars,
+        destruction_over_time,
     }
 }
 
@@ -125,6 +282,96 @@ fn compute_buildings_dps(actions: &[Action], troops: &[Troop]) -> HashMap<Compon
     buildings_damage_per_seconds
 }
 
+/// walls, traps and the buildings that cannot be damaged do not count in the destruction percentage
+fn counts_in_destruction(component: &Component) -> bool {
+    if component.life_points.is_none() {
+        return false;
+    }
+
+    match &component.kind {
+        ComponentType::Building(building) => !building.building_type.is_trap(),
+        ComponentType::Wall(_) => false,
+    }
+}
+
+fn destruction_percentage(destroyed: usize, total: usize) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    destroyed as f32 * 100.0 / total as f32
+}
+
+fn live_defenses(village: &Village) -> Vec<Defense> {
+    village
+        .iter_components()
+        .filter(|(_, _, component)| !component.is_destroyed())
+        .filter_map(|(id, cell, component)| match &component.kind {
+            ComponentType::Building(Building {
+                characteristics: BuildingCharacteristics::Defense(characteristics),
+                ..
+            }) => Some(Defense {
+                id,
+                center: component.center(cell),
+                range: characteristics.range,
+                damage_per_seconds: characteristics.damage_per_seconds,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// defenses keep their target as long as it is alive and in their range,
+/// then they target the closest living troop in their range
+fn update_defenses_targets(
+    defenses: &[Defense],
+    troops: &[Troop],
+    troops_outcomes: &[TroopOutcome],
+    targets: &mut HashMap<ComponentId, TroopId>,
+) {
+    // destroyed defenses have no target
+    targets.retain(|defense_id, _| defenses.iter().any(|defense| defense.id == *defense_id));
+
+    for defense in defenses {
+        let distance = |troop_id: TroopId| troops[troop_id.0].pos.distance(defense.center);
+        let is_targetable = |troop_id: TroopId| {
+            troops_outcomes[troop_id.0].is_alive() && distance(troop_id) <= defense.range
+        };
+
+        if targets.get(&defense.id).is_some_and(|target| is_targetable(*target)) {
+            continue;
+        }
+
+        let closest_troop = (0..troops.len())
+            .map(TroopId)
+            .filter(|troop_id| is_targetable(*troop_id))
+            .min_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+
+        match closest_troop {
+            Some(troop_id) => targets.insert(defense.id, troop_id),
+            None => targets.remove(&defense.id),
+        };
+    }
+}
+
+fn compute_troops_dps(defenses: &[Defense], targets: &HashMap<ComponentId, TroopId>) -> HashMap<TroopId, f32> {
+    let mut troops_damage_per_seconds = HashMap::new();
+
+    for defense in defenses {
+        if let Some(target) = targets.get(&defense.id) {
+            *troops_damage_per_seconds.entry(*target).or_insert(0.0) += defense.damage_per_seconds;
+        }
+    }
+
+    troops_damage_per_seconds
+}
+
+fn get_shortest_troop_death_time(troops_dps: &HashMap<TroopId, f32>, troops_outcomes: &[TroopOutcome]) -> f32 {
+    troops_dps
+        .iter()
+        .map(|(troop_id, dps)| troops_outcomes[troop_id.0].life_points / dps)
+        .fold(f32::MAX, f32::min)
+}
+
 fn move_troops(
     actions: &mut [Action],
     troops: &mut [Troop],
@@ -144,10 +391,15 @@ fn move_troops(
 fn compute_troops_actions(
     village: &Village,
     troops: &[Troop],
+    troops_outcomes: &[TroopOutcome],
     actions: &mut Vec<Action>,
     scenery: &Scenery,
 ) {
     for (troop_id, troop) in troops.iter().enumerate() {
+        if !troops_outcomes[troop_id].is_alive() {
+            continue;
+        }
+
         let troop_id = TroopId(troop_id);
 
         let troop_action = actions.iter_mut().find(|a| a.troop_id == troop_id);
@@ -206,10 +458,24 @@ fn create_move_action(
     village: &Village,
     scenery: &Scenery,
 ) -> Option<Action> {
-    let (path, building_id) = find_route_to_next_building(troop, village, scenery)?;
+    let (mut path, building_id) = find_route_to_next_building(troop, village, scenery)?;
 
+    // the troop stays idle when no path leads to its target
     if path.is_empty() {
-        panic!("Could not find a path to go to building {:?}", building_id);
+        return None;
+    }
+
+    // ranged troops stop walking as soon as the target is in their range
+    let range = troop.tpe.range();
+    if range > 0.0 {
+        let footprint = village.get_component_footprint(building_id).unwrap();
+        let in_range = path
+            .iter()
+            .position(|pos| footprint.iter().any(|cell| cell.distance_with_pos(*pos) <= range));
+
+        if let Some(idx) = in_range {
+            path.truncate(idx + 1);
+        }
     }
 
     Some(Action {
@@ -237,11 +503,12 @@ fn complete_move_action(
         return path.clone();
     }
 
-    // else, if we didn't, cut the action's move path to remove all the completed traveling
+    // else, if we didn't, cut the action's move path to remove all the completed traveling,
+    // the troop is still heading to the position at idx
     let mut new_path = Vec::from(&path[idx..]);
-    new_path[0] = troop_new_pos;
+    new_path.insert(0, troop_new_pos);
 
-    let mut path_walked = Vec::from(&path[..idx - 1]);
+    let mut path_walked = Vec::from(&path[..idx]);
     path_walked.push(troop_new_pos);
 
     action.path = new_path;
@@ -347,3 +614,384 @@ fn follow_path_within_time(path: &[Pos], troop: &Troop, time_limit: f32) -> (Pos
 
     (current_pos, current_pos_idx, total_remaining_time)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::buidling::{Building, BuildingCharacteristics, BuildingType, DefenceCharacteristics};
+    use crate::cell::Cell;
+    use crate::position::Pos;
+    use crate::troop::{Troop, TroopType};
+    use crate::village::{Component, ComponentId, ComponentType, Village};
+    use crate::wall::Wall;
+    use std::collections::HashMap;
+
+    use super::{
+        simulate_attack, update_defenses_targets, AttackPlan, AttackSimulationResult, Defense, TroopOutcome,
+        DEFENSE_RETARGET_INTERVAL,
+    };
+
+    fn town_hall() -> Component {
+        Component {
+            life_points: Some(1000.0),
+            kind: ComponentType::Building(Building {
+                building_type: BuildingType::TownHall,
+                level: 10,
+                characteristics: BuildingCharacteristics::Passive,
+            }),
+        }
+    }
+
+    fn building(building_type: BuildingType, life_points: f32) -> Component {
+        Component {
+            life_points: Some(life_points),
+            kind: ComponentType::Building(Building {
+                building_type,
+                level: 1,
+                characteristics: BuildingCharacteristics::Passive,
+            }),
+        }
+    }
+
+    fn cannon() -> Component {
+        Component {
+            life_points: Some(300.0),
+            kind: ComponentType::Building(Building {
+                building_type: BuildingType::Cannon(Default::default()),
+                level: 1,
+                characteristics: BuildingCharacteristics::Defense(DefenceCharacteristics {
+                    range: 9.0,
+                    damage_per_seconds: 10.0,
+                }),
+            }),
+        }
+    }
+
+    fn troop_at(tpe: TroopType, x: f32, y: f32) -> Troop {
+        Troop {
+            tpe,
+            level: 4,
+            pos: Pos::new(x, y),
+        }
+    }
+
+    fn troop_plan(tpe: TroopType) -> AttackPlan {
+        AttackPlan {
+            initial_placements: vec![troop_at(tpe, 44.0, 44.0)],
+        }
+    }
+
+    fn barbarian_plan() -> AttackPlan {
+        troop_plan(TroopType::Barbarian)
+    }
+
+    fn first_destroyed(result: &AttackSimulationResult) -> Option<ComponentId> {
+        result
+            .evolution_logs
+            .iter()
+            .flat_map(|log| &log.buildings_destroyed)
+            .next()
+            .copied()
+    }
+
+    #[test]
+    fn melee_troop_attacks_next_to_its_target() {
+        let mut village = Village::default();
+        let town_hall_id = village.add_component(Cell::new(20, 35), town_hall()).unwrap();
+
+        let attack_plan = barbarian_plan();
+
+        let result = simulate_attack(180, &village, &attack_plan);
+
+        // the troop stays where it attacked once the town hall is destroyed
+        assert!(result.village.is_component_destroyed(town_hall_id));
+        let attack_pos = result.troops[0].pos;
+        let footprint = village.get_component_footprint(town_hall_id).unwrap();
+        assert!(
+            footprint.iter().any(|cell| cell.distance_with_pos(attack_pos) <= 1.0),
+            "attacked from ({}, {})",
+            attack_pos.x,
+            attack_pos.y
+        );
+    }
+
+    /// walls around the town hall placed at (20, 20)
+    fn surround_with_walls(village: &mut Village, life_points: Option<f32>) {
+        for i in 19..=24 {
+            for cell in [Cell::new(i, 19), Cell::new(i, 24), Cell::new(19, i), Cell::new(24, i)] {
+                let wall = Component {
+                    life_points,
+                    kind: ComponentType::Wall(Wall { level: 1 }),
+                };
+                if village.get_component_at(cell).is_none() {
+                    village.add_component(cell, wall).unwrap();
+                }
+            }
+        }
+    }
+
+    /// a town hall close to the troops and a cannon further away
+    fn town_hall_and_cannon() -> (Village, ComponentId, ComponentId) {
+        let mut village = Village::default();
+        let town_hall_id = village.add_component(Cell::new(36, 36), town_hall()).unwrap();
+        let cannon_id = village.add_component(Cell::new(28, 28), cannon()).unwrap();
+        (village, town_hall_id, cannon_id)
+    }
+
+    #[test]
+    fn giants_go_for_the_defenses() {
+        let (village, _, cannon_id) = town_hall_and_cannon();
+
+        let result = simulate_attack(120, &village, &troop_plan(TroopType::Giant));
+
+        assert_eq!(first_destroyed(&result), Some(cannon_id));
+    }
+
+    #[test]
+    fn barbarians_go_for_the_closest_building() {
+        let (village, town_hall_id, _) = town_hall_and_cannon();
+
+        let result = simulate_attack(180, &village, &barbarian_plan());
+
+        assert_eq!(first_destroyed(&result), Some(town_hall_id));
+    }
+
+    #[test]
+    fn troops_go_for_the_next_building_when_the_closest_is_out_of_reach() {
+        let mut village = Village::default();
+        let town_hall_id = village.add_component(Cell::new(20, 20), town_hall()).unwrap();
+        surround_with_walls(&mut village, None);
+        let gold_mine_id = village
+            .add_component(Cell::new(5, 5), building(BuildingType::GoldMine, 300.0))
+            .unwrap();
+
+        let result = simulate_attack(120, &village, &barbarian_plan());
+
+        assert!(result.village.is_component_destroyed(gold_mine_id));
+        assert!(!result.village.is_component_destroyed(town_hall_id));
+    }
+
+    /// a town hall destroyed in a few seconds and a gold mine out of reach for a short attack
+    fn town_hall_and_far_gold_mine() -> (Village, ComponentId) {
+        let mut village = Village::default();
+        let town_hall_id = village
+            .add_component(Cell::new(36, 36), building(BuildingType::TownHall, 100.0))
+            .unwrap();
+        village
+            .add_component(Cell::new(5, 5), building(BuildingType::GoldMine, 300.0))
+            .unwrap();
+        (village, town_hall_id)
+    }
+
+    #[test]
+    fn half_destruction_and_the_town_hall_give_two_stars() {
+        let (village, _) = town_hall_and_far_gold_mine();
+
+        let result = simulate_attack(30, &village, &barbarian_plan());
+
+        assert_eq!(result.destruction, 50.0);
+        assert_eq!(result.stars, 2);
+    }
+
+    #[test]
+    fn buildings_that_cannot_be_damaged_do_not_count_in_the_destruction() {
+        let mut village = Village::default();
+        village
+            .add_component(Cell::new(36, 36), building(BuildingType::TownHall, 100.0))
+            .unwrap();
+        let laboratory = Component {
+            life_points: None,
+            ..building(BuildingType::Laboratory, 0.0)
+        };
+        village.add_component(Cell::new(5, 5), laboratory).unwrap();
+
+        let result = simulate_attack(30, &village, &barbarian_plan());
+
+        assert_eq!(result.destruction, 100.0);
+        assert_eq!(result.stars, 3);
+    }
+
+    #[test]
+    fn destruction_over_time_follows_the_destroyed_buildings() {
+        let (village, town_hall_id) = town_hall_and_far_gold_mine();
+
+        let result = simulate_attack(30, &village, &barbarian_plan());
+
+        let series = &result.destruction_over_time;
+        assert_eq!(series.len(), result.evolution_logs.len());
+        assert!(series.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(series.last().unwrap().0, 30.0);
+
+        let town_hall_log = result
+            .evolution_logs
+            .iter()
+            .position(|log| log.buildings_destroyed.contains(&town_hall_id))
+            .unwrap();
+        assert!(series[..town_hall_log].iter().all(|(_, destruction)| *destruction == 0.0));
+        assert!(series[town_hall_log..].iter().all(|(_, destruction)| *destruction == 50.0));
+    }
+
+    #[test]
+    fn defenses_kill_the_troops_in_their_range() {
+        let mut village = Village::default();
+        let cannon = Component {
+            life_points: Some(1000.0),
+            ..cannon()
+        };
+        village.add_component(Cell::new(28, 28), cannon).unwrap();
+        let attack_plan = AttackPlan {
+            initial_placements: vec![troop_at(TroopType::Barbarian, 32.0, 32.0)],
+        };
+
+        let result = simulate_attack(30, &village, &attack_plan);
+
+        // 85 life points at level 4, against 10 damages per second
+        let outcome = &result.troops_outcomes[0];
+        assert_eq!(outcome.life_points, 0.0);
+        assert!((outcome.death_time.unwrap() - 8.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn destroyed_defenses_stop_firing() {
+        let mut village = Village::default();
+        let cannon = Component {
+            life_points: Some(20.0),
+            ..cannon()
+        };
+        let cannon_id = village.add_component(Cell::new(28, 28), cannon).unwrap();
+        let attack_plan = AttackPlan {
+            initial_placements: vec![troop_at(TroopType::Barbarian, 32.0, 32.0)],
+        };
+
+        let short_attack = simulate_attack(15, &village, &attack_plan);
+        let long_attack = simulate_attack(30, &village, &attack_plan);
+
+        // the troop is not hurt anymore once the cannon is destroyed
+        assert!(short_attack.village.is_component_destroyed(cannon_id));
+        let life_points = short_attack.troops_outcomes[0].life_points;
+        assert!(life_points > 0.0 && life_points < 85.0);
+        assert_eq!(long_attack.troops_outcomes[0].life_points, life_points);
+    }
+
+    #[test]
+    fn evolutions_last_at_most_the_retarget_interval() {
+        let (village, _, _) = town_hall_and_cannon();
+
+        let result = simulate_attack(60, &village, &troop_plan(TroopType::Giant));
+
+        let mut evolution_start_time = 60.0;
+        for log in &result.evolution_logs {
+            assert!(evolution_start_time - log.evolution_end_time <= DEFENSE_RETARGET_INTERVAL + 1e-4);
+            evolution_start_time = log.evolution_end_time;
+        }
+    }
+
+    #[test]
+    fn defenses_target_the_closest_troop_in_their_range_and_keep_it() {
+        let mut village = Village::default();
+        let defense_id = village.add_component(Cell::new(20, 20), cannon()).unwrap();
+        let defense = Defense {
+            id: defense_id,
+            center: Pos::new(20.0, 20.0),
+            range: 5.0,
+            damage_per_seconds: 10.0,
+        };
+        let mut troops = vec![
+            troop_at(TroopType::Barbarian, 30.0, 20.0),
+            troop_at(TroopType::Barbarian, 24.0, 20.0),
+        ];
+        let outcomes = troops
+            .iter()
+            .map(|troop| TroopOutcome {
+                life_points: troop.hit_points(),
+                death_time: None,
+                damage_dealt: 0.0,
+            })
+            .collect::<Vec<_>>();
+        let mut targets = HashMap::new();
+        let defenses = [defense];
+
+        update_defenses_targets(&defenses, &troops, &outcomes, &mut targets);
+        assert_eq!(targets[&defense_id].0, 1);
+
+        // the target is kept while it is in range, even with a closer troop
+        troops[0].pos = Pos::new(21.0, 20.0);
+        update_defenses_targets(&defenses, &troops, &outcomes, &mut targets);
+        assert_eq!(targets[&defense_id].0, 1);
+
+        // then the defense switches to the closest troop in its range
+        troops[1].pos = Pos::new(40.0, 20.0);
+        update_defenses_targets(&defenses, &troops, &outcomes, &mut targets);
+        assert_eq!(targets[&defense_id].0, 0);
+
+        // destroyed defenses have no target
+        update_defenses_targets(&[], &troops, &outcomes, &mut targets);
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn attack_plans_reject_invalid_troop_levels() {
+        let troop = Troop {
+            level: 42,
+            ..troop_at(TroopType::Giant, 44.0, 44.0)
+        };
+
+        assert!(AttackPlan::new(vec![troop]).is_err());
+        assert!(AttackPlan::new(vec![troop_at(TroopType::Giant, 44.0, 44.0)]).is_ok());
+    }
+
+    #[test]
+    fn damages_shared_on_a_building_sum_to_its_lost_life_points() {
+        let mut village = Village::default();
+        let town_hall_id = village.add_component(Cell::new(20, 35), town_hall()).unwrap();
+        let attack_plan = AttackPlan {
+            initial_placements: vec![
+                troop_at(TroopType::Barbarian, 44.0, 44.0),
+                troop_at(TroopType::Giant, 40.0, 44.0),
+            ],
+        };
+
+        let result = simulate_attack(90, &village, &attack_plan);
+
+        let lost_life_points = 1000.0 - result.village.get_component_lifepoints(town_hall_id).unwrap();
+        let damage_dealt = result.troops_outcomes.iter().map(|outcome| outcome.damage_dealt).sum::<f32>();
+        assert!(lost_life_points > 0.0);
+        assert!((damage_dealt - lost_life_points).abs() < 1e-2);
+        assert!(result.troops_outcomes.iter().all(|outcome| outcome.damage_dealt > 0.0));
+        assert!(result.troops_outcomes.iter().all(|outcome| outcome.death_time.is_none()));
+    }
+
+    #[test]
+    fn ranged_troops_stop_at_their_range() {
+        let mut village = Village::default();
+        let town_hall_id = village.add_component(Cell::new(20, 35), town_hall()).unwrap();
+
+        let result = simulate_attack(120, &village, &troop_plan(TroopType::Archer));
+
+        // the troop stays where it attacked once the town hall is destroyed
+        assert!(result.village.is_component_destroyed(town_hall_id));
+        let attack_pos = result.troops[0].pos;
+        let footprint = village.get_component_footprint(town_hall_id).unwrap();
+        let distance = footprint
+            .iter()
+            .map(|cell| cell.distance_with_pos(attack_pos))
+            .fold(f32::MAX, f32::min);
+        // the path goes from cell to cell, the first one in range is at most a diagonal step closer
+        let range = TroopType::Archer.range();
+        assert!(distance <= range && distance > range - 2f32.sqrt(), "attacked from {distance} cells");
+    }
+
+    #[test]
+    fn troops_break_the_walls_closing_their_target_in() {
+        // the troop stays idle behind the walls that cannot be damaged, a short attack is enough
+        for (wall_life_points, duration) in [(Some(300.0), 300), (None, 30)] {
+            let mut village = Village::default();
+            let town_hall_id = village.add_component(Cell::new(20, 20), town_hall()).unwrap();
+            surround_with_walls(&mut village, wall_life_points);
+
+            let result = simulate_attack(duration, &village, &barbarian_plan());
+
+            let destroyed = result.village.is_component_destroyed(town_hall_id);
+            assert_eq!(destroyed, wall_life_points.is_some());
+        }
+    }
+}
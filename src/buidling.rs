@@ -1,166 +1,395 @@
 use std::ops::RangeInclusive;
 
 use enum_assoc::Assoc;
+use rand::Rng;
 use rand_derive2::RandGen;
+use serde::{Deserialize, Serialize};
 
-#[derive(Assoc, Clone, RandGen)]
+#[derive(Assoc, Clone, RandGen, Serialize, Deserialize)]
 #[func(pub fn name(& self) -> & 'static str)]
 #[func(pub fn plot_size(& self) -> PlotSize)]
 #[func(pub fn self_size(& self) -> PlotSize { self.plot_size() })]
 #[func(pub fn level_range(& self) -> RangeInclusive < u8 >)]
+#[func(pub fn category(& self) -> BuildingCategory)]
+#[func(pub fn is_trap(& self) -> bool { false })]
+#[func(fn hit_points(& self) -> (f32, f32) { (0.0, 0.0) })]
+#[func(fn defense_range(& self) -> Option < f32 > { None })]
+#[func(fn damage_per_seconds(& self) -> (f32, f32) { (0.0, 0.0) })]
 pub enum BuildingType {
     #[assoc(name = "Air_Bomb")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X1Invisible)]
     #[assoc(level_range = 9..=9)]
+    #[assoc(is_trap = true)]
     AirBomb,
 
     #[assoc(name = "Archer_Tower")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(self_size = PlotSize::X2)]
     #[assoc(level_range = 1..=21)]
+    #[assoc(hit_points = (380.0, 2300.0))]
+    #[assoc(defense_range = 10.0)]
+    #[assoc(damage_per_seconds = (11.0, 180.0))]
     ArcherTower(ArcherDefenceState),
 
     #[assoc(name = "Army_Camp")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X4)]
     #[assoc(self_size = PlotSize::X2)]
     #[assoc(level_range = 3..=12)]
+    #[assoc(hit_points = (250.0, 1000.0))]
     ArmyCamp,
 
     #[assoc(name = "Barracks")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=17)]
+    #[assoc(hit_points = (250.0, 1500.0))]
     Barracks,
 
     #[assoc(name = "Blacksmith")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 5..=5)]
+    #[assoc(hit_points = (700.0, 1300.0))]
     Blacksmith,
 
     #[assoc(name = "Builders_Hut")]
+    #[assoc(category = BuildingCategory::Other)]
     #[assoc(plot_size = PlotSize::X2)]
-    #[assoc(level_range = 2..=6)]
+    // the first level sprite has no level in its file name, see get_file_name
+    #[assoc(level_range = 1..=6)]
+    #[assoc(hit_points = (250.0, 1800.0))]
     BuilderHut,
 
     #[assoc(name = "Cannon")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(self_size = PlotSize::X3)]
     #[assoc(level_range = 1..=21)]
+    #[assoc(hit_points = (420.0, 2400.0))]
+    #[assoc(defense_range = 9.0)]
+    #[assoc(damage_per_seconds = (9.0, 200.0))]
     Cannon(MissileDefenceState),
 
     #[assoc(name = "Dark_Barracks")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=11)]
+    #[assoc(hit_points = (250.0, 1300.0))]
     DarkBarracks,
 
     #[assoc(name = "Dark_Elixir_Drill")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 1..=10)]
+    #[assoc(hit_points = (800.0, 1800.0))]
     DarkElixirDrill,
 
     #[assoc(name = "Dark_Elixir_Storage")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=11)]
+    #[assoc(hit_points = (2000.0, 4800.0))]
     DarkElixirStorage,
 
     #[assoc(name = "Dark_Spell_Factory")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 2..=6)]
+    #[assoc(hit_points = (600.0, 1000.0))]
     DarkSpellFactory(SpellFactoryState),
 
     #[assoc(name = "Eagle_Artillery")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X4)]
     #[assoc(self_size = PlotSize::X3)]
     #[assoc(level_range = 2..=7)]
+    #[assoc(hit_points = (4000.0, 5600.0))]
+    #[assoc(defense_range = 50.0)]
+    #[assoc(damage_per_seconds = (30.0, 50.0))]
     EagleArtillery(EagleArtilleryState),
 
     #[assoc(name = "Elixir_Collector")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(self_size = PlotSize::X2)]
     #[assoc(level_range = 1..=16)]
+    #[assoc(hit_points = (400.0, 1300.0))]
     ElixirCollector(ContainerState),
 
     #[assoc(name = "Elixir_Storage")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=17)]
+    #[assoc(hit_points = (400.0, 4200.0))]
     ElixirStorage(ContainerState),
 
     #[assoc(name = "Giant_Bomb")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X2Invisible)]
     #[assoc(level_range = 5..=5)]
+    #[assoc(is_trap = true)]
     GiantBomb(ExplosiveState),
 
     #[assoc(name = "Gold_Mine")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 1..=16)]
+    #[assoc(hit_points = (400.0, 1300.0))]
     GoldMine,
 
     #[assoc(name = "Gold_Storage")]
+    #[assoc(category = BuildingCategory::Resource)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=17)]
+    #[assoc(hit_points = (400.0, 4200.0))]
     GoldStorage(ContainerState),
 
     #[assoc(name = "Hidden_Tesla")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X1Invisible)]
     #[assoc(level_range = 1..=15)]
+    #[assoc(hit_points = (600.0, 1700.0))]
+    #[assoc(defense_range = 7.0)]
+    #[assoc(damage_per_seconds = (34.0, 150.0))]
     HiddenTesla,
 
     #[assoc(name = "Inferno_Tower")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(self_size = PlotSize::X2)]
     #[assoc(level_range = 1..=10)]
+    #[assoc(hit_points = (1500.0, 4000.0))]
+    #[assoc(defense_range = 9.0)]
+    #[assoc(damage_per_seconds = (30.0, 140.0))]
     InfernoTower(InfernoState),
 
     #[assoc(name = "Laboratory")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=14)]
+    #[assoc(hit_points = (500.0, 2000.0))]
     Laboratory,
 
     #[assoc(name = "Mortar")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 1..=16)]
+    #[assoc(hit_points = (400.0, 1800.0))]
+    #[assoc(defense_range = 11.0)]
+    #[assoc(damage_per_seconds = (4.0, 25.0))]
     Mortar(MissileDefenceState),
 
     #[assoc(name = "Pet_House")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=10)]
+    #[assoc(hit_points = (700.0, 1300.0))]
     PetHouse,
 
     #[assoc(name = "Scattershot")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=5)]
+    #[assoc(hit_points = (3600.0, 5100.0))]
+    #[assoc(defense_range = 10.0)]
+    #[assoc(damage_per_seconds = (115.0, 170.0))]
     Scattershot(ScattershotState),
 
     #[assoc(name = "Seeking_Air_Mine")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X1Invisible)]
     #[assoc(self_size = PlotSize::X1Invisible)]
     #[assoc(level_range = 5..=5)]
+    #[assoc(is_trap = true)]
     SeekingAirMine,
 
     #[assoc(name = "Spell_Factory")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 1..=7)]
+    #[assoc(hit_points = (425.0, 1200.0))]
     SpellFactory(SpellFactoryState),
 
     #[assoc(name = "Town_Hall")]
+    #[assoc(category = BuildingCategory::Other)]
     #[assoc(plot_size = PlotSize::X4)]
     #[assoc(self_size = PlotSize::X3)]
     #[assoc(level_range = 3..=16)]
+    #[assoc(hit_points = (450.0, 11000.0))]
     TownHall,
 
     #[assoc(name = "Wizard_Tower")]
+    #[assoc(category = BuildingCategory::Defense)]
     #[assoc(plot_size = PlotSize::X3)]
     #[assoc(level_range = 3..=16)]
+    #[assoc(hit_points = (620.0, 2400.0))]
+    #[assoc(defense_range = 7.0)]
+    #[assoc(damage_per_seconds = (11.0, 72.0))]
     WizardTower,
 
     #[assoc(name = "Workshop")]
+    #[assoc(category = BuildingCategory::Army)]
     #[assoc(plot_size = PlotSize::X4)]
     #[assoc(level_range = 3..=7)]
+    #[assoc(hit_points = (1000.0, 2000.0))]
     Workshop,
 }
 
+#[derive(Assoc, Copy, Clone, Eq, PartialEq, Debug)]
+#[func(pub const fn name(& self) -> & 'static str)]
+pub enum BuildingCategory {
+    #[assoc(name = "defense")]
+    Defense,
+    #[assoc(name = "resource")]
+    Resource,
+    #[assoc(name = "army")]
+    Army,
+    #[assoc(name = "wall")]
+    Wall,
+    #[assoc(name = "other")]
+    Other,
+}
+
+impl BuildingCategory {
+    pub const ALL: [BuildingCategory; 5] = [
+        BuildingCategory::Defense,
+        BuildingCategory::Resource,
+        BuildingCategory::Army,
+        BuildingCategory::Wall,
+        BuildingCategory::Other,
+    ];
+}
+
 pub const BUILDING_ASSETS_FOLDER: &str = "buildings";
 
 impl BuildingType {
+    /// every building type, in their default state
+    pub fn all() -> Vec<BuildingType> {
+        vec![
+            BuildingType::AirBomb,
+            BuildingType::ArcherTower(Default::default()),
+            BuildingType::ArmyCamp,
+            BuildingType::Barracks,
+            BuildingType::Blacksmith,
+            BuildingType::BuilderHut,
+            BuildingType::Cannon(Default::default()),
+            BuildingType::DarkBarracks,
+            BuildingType::DarkElixirDrill,
+            BuildingType::DarkElixirStorage,
+            BuildingType::DarkSpellFactory(Default::default()),
+            BuildingType::EagleArtillery(Default::default()),
+            BuildingType::ElixirCollector(Default::default()),
+            BuildingType::ElixirStorage(Default::default()),
+            BuildingType::GiantBomb(Default::default()),
+            BuildingType::GoldMine,
+            BuildingType::GoldStorage(Default::default()),
+            BuildingType::HiddenTesla,
+            BuildingType::InfernoTower(Default::default()),
+            BuildingType::Laboratory,
+            BuildingType::Mortar(Default::default()),
+            BuildingType::PetHouse,
+            BuildingType::Scattershot(Default::default()),
+            BuildingType::SeekingAirMine,
+            BuildingType::SpellFactory(Default::default()),
+            BuildingType::TownHall,
+            BuildingType::WizardTower,
+            BuildingType::Workshop,
+        ]
+    }
+
+    /// every building type in each of its states, the states having their own sprites
+    pub fn all_states() -> Vec<BuildingType> {
+        BuildingType::all()
+            .into_iter()
+            .flat_map(|building_type| match building_type {
+                BuildingType::DarkSpellFactory(_) => {
+                    SpellFactoryState::ALL.map(BuildingType::DarkSpellFactory).to_vec()
+                }
+                BuildingType::EagleArtillery(_) => {
+                    EagleArtilleryState::ALL.map(BuildingType::EagleArtillery).to_vec()
+                }
+                BuildingType::GiantBomb(_) => ExplosiveState::ALL.map(BuildingType::GiantBomb).to_vec(),
+                BuildingType::InfernoTower(_) => InfernoState::ALL.map(BuildingType::InfernoTower).to_vec(),
+                BuildingType::Scattershot(_) => ScattershotState::ALL.map(BuildingType::Scattershot).to_vec(),
+                BuildingType::SpellFactory(_) => SpellFactoryState::ALL.map(BuildingType::SpellFactory).to_vec(),
+                building_type => vec![building_type],
+            })
+            .collect()
+    }
+
+    /// the same building type in a random state
+    pub fn with_random_state(self, rng: &mut impl Rng) -> BuildingType {
+        match self {
+            BuildingType::ArcherTower(_) => BuildingType::ArcherTower(rng.gen()),
+            BuildingType::Cannon(_) => BuildingType::Cannon(rng.gen()),
+            BuildingType::DarkSpellFactory(_) => BuildingType::DarkSpellFactory(rng.gen()),
+            BuildingType::EagleArtillery(_) => BuildingType::EagleArtillery(rng.gen()),
+            BuildingType::ElixirCollector(_) => BuildingType::ElixirCollector(rng.gen()),
+            BuildingType::ElixirStorage(_) => BuildingType::ElixirStorage(rng.gen()),
+            BuildingType::GiantBomb(_) => BuildingType::GiantBomb(rng.gen()),
+            BuildingType::GoldStorage(_) => BuildingType::GoldStorage(rng.gen()),
+            BuildingType::InfernoTower(_) => BuildingType::InfernoTower(rng.gen()),
+            BuildingType::Mortar(_) => BuildingType::Mortar(rng.gen()),
+            BuildingType::Scattershot(_) => BuildingType::Scattershot(rng.gen()),
+            BuildingType::SpellFactory(_) => BuildingType::SpellFactory(rng.gen()),
+            building_type => building_type,
+        }
+    }
+
+    /// finds the type and level of a sprite file name, the state suffix is ignored
+    pub fn from_file_name(file_name: &str) -> Option<(BuildingType, u8)> {
+        let file_stem = file_name.strip_suffix(".png")?;
+
+        BuildingType::all()
+            .into_iter()
+            .find_map(|building_type| {
+                let rest = file_stem.strip_prefix(building_type.name())?;
+                let digits = rest.chars().take_while(char::is_ascii_digit).collect::<String>();
+                let level = if digits.is_empty() {
+                    // only the first level of the builder hut has no level in its file name
+                    if !rest.is_empty() {
+                        return None;
+                    }
+                    1
+                } else {
+                    digits.parse().ok()?
+                };
+                Some((building_type, level))
+            })
+    }
+
+    /// life points of the building at the given level, None for the traps that cannot be damaged
+    pub fn life_points(&self, level: u8) -> Option<f32> {
+        if self.is_trap() {
+            return None;
+        }
+        Some(self.level_stat(self.hit_points(), level))
+    }
+
+    /// what the building does during an attack at the given level
+    pub fn characteristics(&self, level: u8) -> BuildingCharacteristics {
+        match self.defense_range() {
+            Some(range) => BuildingCharacteristics::Defense(DefenceCharacteristics {
+                range,
+                damage_per_seconds: self.level_stat(self.damage_per_seconds(), level),
+            }),
+            None => BuildingCharacteristics::Passive,
+        }
+    }
+
+    /// stat interpolated between its first level value and its last level value
+    fn level_stat(&self, (first, last): (f32, f32), level: u8) -> f32 {
+        let max_level = *self.level_range().end();
+        if max_level <= 1 {
+            return last;
+        }
+        let progress = (level.clamp(1, max_level) - 1) as f32 / (max_level - 1) as f32;
+        (first + (last - first) * progress).round()
+    }
+
     pub fn get_file_path(&self, level: u8) -> String {
         let file_name = self.get_file_name(level);
 
@@ -234,7 +463,7 @@ trait DefenceState {
     fn get_as_suffix(self) -> &'static str;
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum ArcherDefenceState {
     #[default]
     Regular,
@@ -252,7 +481,7 @@ impl DefenceState for ArcherDefenceState {
     }
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum MissileDefenceState {
     #[default]
     Regular,
@@ -270,7 +499,7 @@ impl DefenceState for MissileDefenceState {
     }
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum EagleArtilleryState {
     #[default]
     Loaded,
@@ -278,6 +507,14 @@ pub enum EagleArtilleryState {
     Unloaded,
 }
 
+impl EagleArtilleryState {
+    pub const ALL: [EagleArtilleryState; 3] = [
+        EagleArtilleryState::Loaded,
+        EagleArtilleryState::HeadDown,
+        EagleArtilleryState::Unloaded,
+    ];
+}
+
 impl DefenceState for EagleArtilleryState {
     fn get_as_suffix(self) -> &'static str {
         match self {
@@ -288,13 +525,20 @@ impl DefenceState for EagleArtilleryState {
     }
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum SpellFactoryState {
     #[default]
     Inactive,
     Active,
 }
 
+impl SpellFactoryState {
+    pub const ALL: [SpellFactoryState; 2] = [
+        SpellFactoryState::Inactive,
+        SpellFactoryState::Active,
+    ];
+}
+
 impl DefenceState for SpellFactoryState {
     fn get_as_suffix(self) -> &'static str {
         match self {
@@ -304,7 +548,7 @@ impl DefenceState for SpellFactoryState {
     }
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum ContainerState {
     #[default]
     Empty,
@@ -320,13 +564,20 @@ impl DefenceState for ContainerState {
     }
 }
 
-#[derive(Default, Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum ExplosiveState {
     #[default]
     Armed,
     Unarmed,
 }
 
+impl ExplosiveState {
+    pub const ALL: [ExplosiveState; 2] = [
+        ExplosiveState::Armed,
+        ExplosiveState::Unarmed,
+    ];
+}
+
 impl DefenceState for ExplosiveState {
     fn get_as_suffix(self) -> &'static str {
         match self {
@@ -336,14 +587,24 @@ impl DefenceState for ExplosiveState {
     }
 }
 
-#[derive(Copy, Clone, RandGen)]
+#[derive(Default, Copy, Clone, RandGen, Serialize, Deserialize)]
 pub enum InfernoState {
+    #[default]
     Multi,
     MultiDepleted,
     Single,
     SingleDepleted,
 }
 
+impl InfernoState {
+    pub const ALL: [InfernoState; 4] = [
+        InfernoState::Multi,
+        InfernoState::MultiDepleted,
+        InfernoState::Single,
+        InfernoState::SingleDepleted,
+    ];
+}
+
 impl DefenceState for InfernoState {
     fn get_as_suffix(self) -> &'static str {
         match self {
@@ -355,13 +616,20 @@ impl DefenceState for InfernoState {
     }
 }
 
-#[derive(Copy, Default, Clone, RandGen)]
+#[derive(Copy, Default, Clone, RandGen, Serialize, Deserialize)]
 pub enum ScattershotState {
     #[default]
     Regular,
     Depleted,
 }
 
+impl ScattershotState {
+    pub const ALL: [ScattershotState; 2] = [
+        ScattershotState::Regular,
+        ScattershotState::Depleted,
+    ];
+}
+
 impl DefenceState for ScattershotState {
     fn get_as_suffix(self) -> &'static str {
         match self {
@@ -371,7 +639,7 @@ impl DefenceState for ScattershotState {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Building {
     pub building_type: BuildingType,
     pub level: u8,
@@ -379,14 +647,15 @@ pub struct Building {
 }
 
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum BuildingCharacteristics {
     Passive,
     Defense(DefenceCharacteristics),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DefenceCharacteristics {
-    range: f32,
-    damage_per_seconds: f32,
+    /// in cells, from the center of the building
+    pub range: f32,
+    pub damage_per_seconds: f32,
 }
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use crate::position::Pos;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct Cell {
     pub x: i16,
     pub y: i16,
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+
+use crate::buidling::{BuildingCategory, BuildingType};
+use crate::validation::{MAX_TOWN_HALL_LEVEL, max_wall_level};
+use crate::wall::WallConnectionType;
+
+lazy_static! {
+    static ref SPRITE_CLASSES: ClassRegistry = Taxonomy::Sprite.load_registry();
+    static ref TYPE_CLASSES: ClassRegistry = Taxonomy::Type.load_registry();
+    static ref TYPE_LEVEL_CLASSES: ClassRegistry = Taxonomy::TypeLevel.load_registry();
+    static ref CATEGORY_CLASSES: ClassRegistry = Taxonomy::Category.load_registry();
+}
+
+/// Granularity of the classes of the labels
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, ValueEnum)]
+pub enum Taxonomy {
+    /// one class per sprite file: type, level and state
+    #[default]
+    Sprite,
+    /// one class per building type
+    Type,
+    /// one class per building type and level
+    TypeLevel,
+    /// defense, resource, army, wall or other
+    Category,
+}
+
+impl Taxonomy {
+    fn registry_file(self) -> &'static str {
+        match self {
+            Taxonomy::Sprite => "assets/classes.json",
+            Taxonomy::Type => "assets/classes_type.json",
+            Taxonomy::TypeLevel => "assets/classes_type_level.json",
+            Taxonomy::Category => "assets/classes_category.json",
+        }
+    }
+
+    fn load_registry(self) -> ClassRegistry {
+        ClassRegistry::load(self.registry_file()).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// every class the village renderer can label, buildings first
+    fn class_names(self) -> Vec<String> {
+        let mut names = self.building_class_names();
+        for name in self.wall_class_names() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    fn building_class_names(self) -> Vec<String> {
+        match self {
+            Taxonomy::Sprite => {
+                // the sprites of every level and state of every building, as loaded from BUILDING_ASSETS_FOLDER
+                let mut names = BuildingType::all_states()
+                    .iter()
+                    .flat_map(|building_type| {
+                        building_type
+                            .level_range()
+                            .map(|level| self.class_name(building_type, level))
+                    })
+                    .collect::<Vec<_>>();
+                names.sort();
+                names
+            }
+            Taxonomy::Type => BuildingType::all()
+                .iter()
+                .map(|building_type| building_type.name().to_string())
+                .collect(),
+            Taxonomy::TypeLevel => BuildingType::all()
+                .iter()
+                .flat_map(|building_type| {
+                    building_type
+                        .level_range()
+                        .map(|level| self.class_name(building_type, level))
+                })
+                .collect(),
+            Taxonomy::Category => BuildingCategory::ALL
+                .iter()
+                .map(|category| category.name().to_string())
+                .collect(),
+        }
+    }
+
+    fn wall_class_names(self) -> Vec<String> {
+        let mut names = Vec::new();
+        for level in 1..=max_wall_level(MAX_TOWN_HALL_LEVEL) {
+            for connection in WallConnectionType::ALL {
+                let name = self.wall_class_name(level, connection);
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.push(self.wall_segment_class_name());
+        names
+    }
+
+    /// kind of the components of the class, "wall" or "building"
+    pub fn component_kind(self, class_name: &str) -> &'static str {
+        if self.wall_class_names().iter().any(|name| name == class_name) {
+            "wall"
+        } else {
+            "building"
+        }
+    }
+
+    /// checks that every class the village renderer can label is in the registry of the taxonomy
+    pub fn check_classes(self) -> Result<(), ClassRegistryError> {
+        let registry = ClassRegistry::load(self.registry_file())?;
+        for name in self.class_names() {
+            registry.class_id(&name)?;
+        }
+        Ok(())
+    }
+
+    pub fn registry(self) -> &'static ClassRegistry {
+        match self {
+            Taxonomy::Sprite => &SPRITE_CLASSES,
+            Taxonomy::Type => &TYPE_CLASSES,
+            Taxonomy::TypeLevel => &TYPE_LEVEL_CLASSES,
+            Taxonomy::Category => &CATEGORY_CLASSES,
+        }
+    }
+
+    pub fn class_name(self, building_type: &BuildingType, level: u8) -> String {
+        match self {
+            Taxonomy::Sprite => building_type.get_file_name(level),
+            Taxonomy::Type => building_type.name().to_string(),
+            Taxonomy::TypeLevel => format!("{}_{level}", building_type.name()),
+            Taxonomy::Category => building_type.category().name().to_string(),
+        }
+    }
+
+    pub fn wall_class_name(self, level: u8, connection: WallConnectionType) -> String {
+        match self {
+            Taxonomy::Sprite => format!("Wall{level}_{}", connection.name()),
+            Taxonomy::Type => "Wall".to_string(),
+            Taxonomy::TypeLevel => format!("Wall_{level}"),
+            Taxonomy::Category => BuildingCategory::Wall.name().to_string(),
+        }
+    }
+
+    pub fn wall_segment_class_name(self) -> String {
+        match self {
+            Taxonomy::Category => BuildingCategory::Wall.name().to_string(),
+            _ => "Wall_Segment".to_string(),
+        }
+    }
+
+    /// class id of a building, panics if the class is not in the registry of the taxonomy
+    pub fn class_id(self, building_type: &BuildingType, level: u8) -> usize {
+        self.named_class_id(&self.class_name(building_type, level))
+    }
+
+    /// panics if the class is not in the registry of the taxonomy
+    pub fn named_class_id(self, name: &str) -> usize {
+        self.registry()
+            .class_id(name)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// Fixed id of every class, so that ids do not depend on the sprites installed on the machine.
+/// The registry files are committed in assets/ and are the source of truth,
+/// new classes have to be added to them by hand with the next free id
+pub struct ClassRegistry {
+    path: PathBuf,
+    /// class names, indexed by class id
+    names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum ClassRegistryError {
+    Read { path: PathBuf, err: std::io::Error },
+    Json(serde_json::Error),
+    /// the ids of the registry must go from 0 to the number of classes - 1
+    InvalidIds(PathBuf),
+    MissingClass { name: String, registry: PathBuf },
+}
+
+impl Display for ClassRegistryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClassRegistryError::Read { path, err } => {
+                write!(f, "could not read the class registry {}: {err}", path.display())
+            }
+            ClassRegistryError::Json(err) => write!(f, "{err}"),
+            ClassRegistryError::InvalidIds(path) => write!(
+                f,
+                "class ids of {} must be unique and go from 0 to the number of classes - 1",
+                path.display()
+            ),
+            ClassRegistryError::MissingClass { name, registry } => write!(
+                f,
+                "class {name} is not in the class registry, add it to {} with the next free id",
+                registry.display()
+            ),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ClassRegistryError {
+    fn from(value: serde_json::Error) -> Self {
+        ClassRegistryError::Json(value)
+    }
+}
+
+impl ClassRegistry {
+    /// loads a committed registry file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ClassRegistryError> {
+        let path = path.as_ref();
+
+        let content = std::fs::read_to_string(path).map_err(|err| ClassRegistryError::Read {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        let ids: BTreeMap<String, usize> = serde_json::from_str(&content)?;
+
+        let mut names = vec![None; ids.len()];
+        for (name, id) in ids {
+            match names.get_mut(id) {
+                Some(slot @ None) => *slot = Some(name),
+                _ => return Err(ClassRegistryError::InvalidIds(path.to_path_buf())),
+            }
+        }
+
+        Ok(ClassRegistry {
+            path: path.to_path_buf(),
+            names: names.into_iter().map(Option::unwrap).collect(),
+        })
+    }
+
+    pub fn class_id(&self, name: &str) -> Result<usize, ClassRegistryError> {
+        self.names
+            .iter()
+            .position(|registered| registered == name)
+            .ok_or_else(|| ClassRegistryError::MissingClass {
+                name: name.to_string(),
+                registry: self.path.clone(),
+            })
+    }
+
+    /// class names, indexed by class id
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn committed_registries_have_every_class() {
+        for taxonomy in Taxonomy::value_variants() {
+            if let Err(err) = taxonomy.check_classes() {
+                panic!("{taxonomy:?}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::path::PathBuf;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::error::ErrorKind;
+
+use crate::cell::Cell;
+use crate::class_registry::Taxonomy;
+use crate::dataset::labels::{LabelFormat, OccludedLabels};
+use crate::dataset::overlay::UiPanel;
+use crate::dataset::tiling::Tiling;
+use crate::label::{BoundsMode, WallLabels};
+
+#[derive(Parser)]
+#[command(about = "Generates, renders and attacks Clash of Clans villages")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// generate a YOLO dataset of rendered villages
+    GenerateVillages(DatasetArgs),
+
+    /// generate a YOLO dataset of building sprites spread over a grid
+    GenerateAssetMess(DatasetArgs),
+
+    /// generate a village and save its layout file
+    GenerateLayout {
+        /// level of the town hall, the other buildings follow it
+        #[arg(long, default_value_t = 10)]
+        town_hall_level: u8,
+
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// village layout file (.json or .ron)
+        #[arg(short, long, default_value = "out/village.ron")]
+        output: PathBuf,
+    },
+
+    /// edit the components of a village layout file.
+    /// The components are found by any cell they cover, they are removed, then moved, then leveled
+    Edit {
+        /// village layout file (.json or .ron)
+        layout: PathBuf,
+
+        /// removes the component covering the cell, as `x,y`
+        #[arg(long, value_parser = parse_cell)]
+        remove: Vec<Cell>,
+
+        /// moves the component covering the first cell to the origin cell, as `x,y:origin_x,origin_y`
+        #[arg(long = "move", value_parser = parse_move)]
+        moves: Vec<(Cell, Cell)>,
+
+        /// changes the level of the component covering the cell, as `x,y:level`
+        #[arg(long = "set-level", value_parser = parse_level)]
+        levels: Vec<(Cell, u8)>,
+
+        /// edited layout file, the layout file is overwritten if not set
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// render a village layout file
+    Render {
+        /// village layout file (.json or .ron)
+        layout: PathBuf,
+
+        #[arg(short, long, default_value = "out/render.png")]
+        output: PathBuf,
+    },
+
+    /// simulate an attack plan against a village layout and render the troops paths
+    Simulate {
+        /// village layout file (.json or .ron)
+        layout: PathBuf,
+
+        /// attack plan file (.json or .ron)
+        plan: PathBuf,
+
+        /// duration of the attack, in seconds
+        #[arg(long, default_value_t = 63)]
+        duration: u32,
+
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        #[arg(short, long, default_value = "out/simulations/simulation.png")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct DatasetArgs {
+    /// number of images the ratios of each split are applied on
+    #[arg(long, default_value_t = 20000)]
+    pub image_count: usize,
+
+    #[arg(long, default_value_t = 0.8)]
+    pub train_ratio: f32,
+
+    #[arg(long, default_value_t = 0.2)]
+    pub val_ratio: f32,
+
+    #[arg(long, default_value_t = 0.2)]
+    pub test_ratio: f32,
+
+    /// root directory of the dataset
+    #[arg(short, long, default_value = "out")]
+    pub output: PathBuf,
+
+    /// dataset root written in data.yaml, the splits are resolved from the directory of data.yaml if not set
+    #[arg(long)]
+    pub dataset_path: Option<PathBuf>,
+
+    /// formats of the written labels, can be repeated
+    #[arg(long = "label-format", value_enum, default_values_t = [LabelFormat::Yolo])]
+    pub label_formats: Vec<LabelFormat>,
+
+    /// how the boxes of the labels are computed from the rendered sprites
+    #[arg(long, value_enum, default_value_t = BoundsMode::Tight)]
+    pub bounds: BoundsMode,
+
+    /// granularity of the classes
+    #[arg(long, value_enum, default_value_t = Taxonomy::Sprite)]
+    pub taxonomy: Taxonomy,
+
+    /// how the walls are labeled
+    #[arg(long, value_enum, default_value_t = WallLabels::None)]
+    pub wall_labels: WallLabels,
+
+    /// cuts every render into square tiles of this size, in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_size: Option<u32>,
+
+    /// fraction of a tile shared with its neighbours
+    #[arg(long, default_value_t = 0.2)]
+    pub tile_overlap: f32,
+
+    /// minimum fraction of a box that must be inside a tile to be labeled in it
+    #[arg(long, default_value_t = 0.5)]
+    pub min_tile_coverage: f32,
+
+    /// minimum fraction of a building left visible by the sprites drawn over it
+    #[arg(long, default_value_t = 0.1)]
+    pub min_visibility: f32,
+
+    /// what happens to the labels of the buildings under the minimum visibility
+    #[arg(long, value_enum, default_value_t = OccludedLabels::Drop)]
+    pub occluded: OccludedLabels,
+
+    #[command(flatten)]
+    pub augmentations: AugmentationArgs,
+
+    /// number of images generated at the same time
+    #[arg(long, default_value_t = 20, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub workers: usize,
+
+    /// skips the entries completed by a previous run with the same arguments
+    #[arg(long)]
+    pub resume: bool,
+
+    /// master seed of the dataset, the same seed always produces the same images and labels
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+}
+
+/// Probability of each augmentation of the second image of every entry,
+/// no augmented image is generated if they are all 0
+#[derive(Args)]
+pub struct AugmentationArgs {
+    /// zoom in of up to 30% followed by a crop back to the image size
+    #[arg(long, default_value_t = 0.0)]
+    pub scale_crop_probability: f32,
+
+    #[arg(long, default_value_t = 0.0)]
+    pub flip_probability: f32,
+
+    /// in-game interface panels drawn over the village, hiding the buildings under them
+    #[arg(long, default_value_t = 0.0)]
+    pub ui_overlay_probability: f32,
+
+    /// panels of the interface overlay
+    #[arg(long = "ui-panel", value_enum, default_values_t = UiPanel::value_variants().to_vec())]
+    pub ui_panels: Vec<UiPanel>,
+
+    /// brightness, contrast and saturation jitter
+    #[arg(long, default_value_t = 0.0)]
+    pub color_jitter_probability: f32,
+
+    #[arg(long, default_value_t = 0.0)]
+    pub blur_probability: f32,
+
+    #[arg(long, default_value_t = 0.0)]
+    pub noise_probability: f32,
+
+    /// white dots drawn over the image
+    #[arg(long, default_value_t = 1.0)]
+    pub dots_probability: f32,
+
+    /// JPEG recompression, applied after every other augmentation
+    #[arg(long, default_value_t = 0.0)]
+    pub jpeg_probability: f32,
+}
+
+impl DatasetArgs {
+    /// exits with a usage error if the arguments cannot be used together
+    pub fn validate(&self) {
+        if self.label_formats.contains(&LabelFormat::Yolo) && self.label_formats.contains(&LabelFormat::YoloSeg) {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "the yolo and yolo-seg label formats both write the txt label files",
+                )
+                .exit()
+        }
+
+        let augmentations = &self.augmentations;
+        let probabilities = [
+            augmentations.scale_crop_probability,
+            augmentations.flip_probability,
+            augmentations.ui_overlay_probability,
+            augmentations.color_jitter_probability,
+            augmentations.blur_probability,
+            augmentations.noise_probability,
+            augmentations.dots_probability,
+            augmentations.jpeg_probability,
+        ];
+        if probabilities.iter().any(|probability| !(0.0..=1.0).contains(probability)) {
+            Cli::command()
+                .error(ErrorKind::ValueValidation, "the augmentation probabilities must be in [0, 1]")
+                .exit()
+        }
+
+        if !(0.0..1.0).contains(&self.tile_overlap) {
+            Cli::command()
+                .error(ErrorKind::ValueValidation, "the tile overlap must be in [0, 1)")
+                .exit()
+        }
+    }
+
+    pub fn tiling(&self) -> Option<Tiling> {
+        self.tile_size.map(|size| Tiling {
+            size,
+            overlap: self.tile_overlap,
+            min_coverage: self.min_tile_coverage,
+        })
+    }
+
+    /// the name of each split with its number of images
+    pub fn splits(&self) -> [(&'static str, usize); 3] {
+        let count = |ratio: f32| (self.image_count as f32 * ratio) as usize;
+
+        [
+            ("train", count(self.train_ratio)),
+            ("val", count(self.val_ratio)),
+            ("test", count(self.test_ratio)),
+        ]
+    }
+}
+
+fn parse_cell(value: &str) -> Result<Cell, String> {
+    let (x, y) = value.split_once(',').ok_or(format!("expected x,y, got {value}"))?;
+    let coordinate = |c: &str| c.trim().parse::<i16>().map_err(|err| format!("invalid coordinate {c}: {err}"));
+
+    Ok(Cell::new(coordinate(x)?, coordinate(y)?))
+}
+
+fn parse_move(value: &str) -> Result<(Cell, Cell), String> {
+    let (from, to) = value.split_once(':').ok_or(format!("expected x,y:origin_x,origin_y, got {value}"))?;
+
+    Ok((parse_cell(from)?, parse_cell(to)?))
+}
+
+fn parse_level(value: &str) -> Result<(Cell, u8), String> {
+    let (cell, level) = value.split_once(':').ok_or(format!("expected x,y:level, got {value}"))?;
+    let level = level.trim().parse().map_err(|err| format!("invalid level {level}: {err}"))?;
+
+    Ok((parse_cell(cell)?, level))
+}
@@ -0,0 +1,353 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::assets_render;
+use crate::assets_render::Asset;
+use crate::buidling::{BUILDING_ASSETS_FOLDER, BuildingType};
+use crate::class_registry::Taxonomy;
+use crate::cli::DatasetArgs;
+use crate::dataset::augmentation::AugmentationPipeline;
+use crate::dataset::jobs::{DatasetError, DatasetResult, run_jobs};
+use crate::dataset::labels::{LabeledImage, LabelFormat, LabelWriter, OccludedLabels};
+use crate::dataset::tiling::Tiling;
+use crate::generator;
+use crate::label::LabelOptions;
+use crate::render::{cache, render, RenderOptions, RenderedScenery};
+use crate::scenery::grid_scenery;
+use crate::seed;
+use crate::seed::SeededRng;
+
+pub mod augmentation;
+pub mod jobs;
+pub mod labels;
+pub mod overlay;
+pub mod tiling;
+
+const ASSETS_FOLDERS: [&str; 1] = [BUILDING_ASSETS_FOLDER];
+
+/// lists the sprites of the assets folders with their class in the given taxonomy
+fn load_assets(taxonomy: Taxonomy) -> Vec<Asset> {
+    let mut files = Vec::new();
+    for folder in ASSETS_FOLDERS {
+        for file in std::fs::read_dir(folder).unwrap() {
+            files.push(file.unwrap().path());
+        }
+    }
+    // the same seed must pick the same assets on every machine
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|path| {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+            let class = match taxonomy {
+                Taxonomy::Sprite => taxonomy
+                    .registry()
+                    .class_id(&file_name)
+                    .unwrap_or_else(|err| panic!("{err}")),
+                _ => {
+                    let (building_type, level) = BuildingType::from_file_name(&file_name)
+                        .unwrap_or_else(|| panic!("no building type matches the sprite {file_name}"));
+                    taxonomy.class_id(&building_type, level)
+                }
+            };
+
+            Asset {
+                path: path.display().to_string(),
+                class,
+            }
+        })
+        .collect()
+}
+
+/// Everything a job needs to generate one entry of the dataset
+pub struct DatasetEntry {
+    pub image_dir: String,
+    pub labels_dir: String,
+    /// directory of the parameters of the augmentations applied to each image
+    pub augmentations_dir: String,
+    /// directory of the markers of the entries whose files are all written
+    pub progress_dir: String,
+    pub split: &'static str,
+    pub id: usize,
+    pub rng: SeededRng,
+    pub label_options: LabelOptions,
+    pub min_visibility: f32,
+    pub occluded: OccludedLabels,
+    pub tiling: Option<Tiling>,
+    pub augmentations: Arc<AugmentationPipeline>,
+    pub label_writers: Arc<Vec<Box<dyn LabelWriter>>>,
+}
+
+impl DatasetEntry {
+    pub fn name(&self) -> String {
+        format!("village_{}", self.id)
+    }
+
+    fn augmented_name(&self) -> String {
+        format!("{}_augmented", self.name())
+    }
+
+    /// written once every file of the entry is, the entries interrupted while writing have no marker
+    fn done_marker(&self) -> PathBuf {
+        Path::new(&self.progress_dir).join(format!("{}.done", self.name()))
+    }
+}
+
+pub async fn assets_mess_generation(args: &DatasetArgs) {
+    // fails before spawning the jobs if an asset has no class
+    let assets = Arc::new(load_assets(args.taxonomy));
+
+    generate_dataset(args, move |entry| {
+        generate_asset_mess_image(&assets, entry)
+    })
+        .await;
+}
+
+fn generate_asset_mess_image(assets: &[Asset], mut entry: DatasetEntry) -> DatasetResult<()> {
+    const ASSETS_PER_IMAGE: u16 = 75;
+
+    let mut used_assets = Vec::new();
+
+    for _ in 0..ASSETS_PER_IMAGE {
+        used_assets.push(&assets[entry.rng.gen_range(0..assets.len())])
+    }
+
+    let grid = cache::image(&grid_scenery().params().image_path);
+    let result = assets_render::render(
+        grid.as_ref().clone(),
+        &used_assets,
+        entry.label_options.bounds,
+        &mut entry.rng,
+    )
+        .map_err(DatasetError::Render)?;
+
+    generate_dataset_entries(result, entry)
+}
+
+pub async fn village_generation(args: &DatasetArgs) {
+    // fails before spawning the jobs if the generator can draw a building or wall with no class
+    args.taxonomy.check_classes().unwrap_or_else(|err| panic!("{err}"));
+
+    generate_dataset(args, generate_village_image).await;
+}
+
+/// generates the entries of every split on a pool of `args.workers` threads
+async fn generate_dataset(
+    args: &DatasetArgs,
+    generate_entries: impl Fn(DatasetEntry) -> DatasetResult<()> + Send + Sync + 'static,
+) {
+    args.validate();
+
+    let output = args.output.display();
+
+    let class_names = Arc::new(args.taxonomy.registry().names().to_vec());
+    let label_writers = Arc::new(
+        args.label_formats
+            .iter()
+            .map(|format| format.writer(class_names.clone(), args.taxonomy))
+            .collect::<Vec<_>>(),
+    );
+
+    let label_options = LabelOptions {
+        bounds: args.bounds,
+        taxonomy: args.taxonomy,
+        walls: args.wall_labels,
+    };
+    let augmentations = Arc::new(AugmentationPipeline::from_args(&args.augmentations, label_options));
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+
+    for (split, image_count) in args.splits() {
+        let image_dir = format!("{output}/images/{split}");
+        let labels_dir = format!("{output}/labels/{split}");
+        let augmentations_dir = format!("{output}/augmentations/{split}");
+        let progress_dir = format!("{output}/progress/{split}");
+
+        std::fs::create_dir_all(&image_dir).unwrap();
+        std::fs::create_dir_all(&labels_dir).unwrap();
+        std::fs::create_dir_all(&progress_dir).unwrap();
+        if !augmentations.is_empty() {
+            std::fs::create_dir_all(&augmentations_dir).unwrap();
+        }
+
+        for x in 0..image_count {
+            let entry = DatasetEntry {
+                image_dir: image_dir.clone(),
+                labels_dir: labels_dir.clone(),
+                augmentations_dir: augmentations_dir.clone(),
+                progress_dir: progress_dir.clone(),
+                split,
+                id: x,
+                rng: seed::entry_rng(args.seed, split, x),
+                label_options,
+                min_visibility: args.min_visibility,
+                occluded: args.occluded,
+                tiling: args.tiling(),
+                augmentations: augmentations.clone(),
+                label_writers: label_writers.clone(),
+            };
+
+            // an interrupted entry has no marker, it is generated again
+            if args.resume && entry.done_marker().exists() {
+                skipped += 1;
+                continue;
+            }
+            entries.push(entry);
+        }
+    }
+
+    if skipped > 0 {
+        println!("Skipping {skipped} entries generated by a previous run");
+    }
+
+    let report = run_jobs(entries, args.workers, skipped, move |entry| {
+        let done_marker = entry.done_marker();
+        // the files of the entry are about to be overwritten
+        if done_marker.exists() {
+            std::fs::remove_file(&done_marker)?;
+        }
+        generate_entries(entry)?;
+        std::fs::write(done_marker, "")?;
+        Ok(())
+    })
+        .await;
+
+    for (split, image_count) in args.splits() {
+        if image_count == 0 {
+            continue;
+        }
+
+        let labels_dir = args.output.join("labels").join(split);
+        for writer in label_writers.iter() {
+            writer.finish_split(&args.output, split, &labels_dir).unwrap();
+        }
+    }
+
+    if args.label_formats.contains(&LabelFormat::Yolo) || args.label_formats.contains(&LabelFormat::YoloSeg) {
+        println!("Generating YOLO dataset configuration");
+        write_dataset_config(args, &class_names);
+    }
+
+    println!("Generated {} entries", report.generated);
+    if report.failed > 0 {
+        eprintln!("{} entries failed, run the same command with --resume to generate them again", report.failed);
+        std::process::exit(1);
+    }
+}
+
+/// writes the `data.yaml` file read by Ultralytics.
+/// Splits paths are relative to the `path` entry, which defaults to the directory of the yaml file
+fn write_dataset_config(args: &DatasetArgs, class_names: &[String]) {
+    let mut dataset_file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(args.output.join("data.yaml"))
+        .expect("cannot open file");
+
+    if let Some(dataset_path) = &args.dataset_path {
+        writeln!(dataset_file, "path: {}", yaml_string(&dataset_path.display().to_string())).unwrap();
+    }
+
+    for (split, image_count) in args.splits() {
+        if image_count > 0 {
+            writeln!(dataset_file, "{split}: images/{split}").unwrap();
+        }
+    }
+
+    writeln!(dataset_file, "nc: {}", class_names.len()).unwrap();
+    writeln!(dataset_file, "names:").unwrap();
+
+    for (class, name) in class_names.iter().enumerate() {
+        writeln!(dataset_file, "  {class}: {}", yaml_string(name)).unwrap();
+    }
+}
+
+/// json strings are valid yaml double-quoted strings
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn generate_village_image(mut entry: DatasetEntry) -> DatasetResult<()> {
+    let town_hall_level = entry.rng.gen_range(BuildingType::TownHall.level_range());
+    let village = generator::generate_village(&mut entry.rng, town_hall_level)?;
+
+    let options = RenderOptions {
+        labels: entry.label_options,
+        show_life_points: false,
+    };
+    let result = render(&village, options).map_err(DatasetError::Render)?;
+
+    generate_dataset_entries(result, entry)
+}
+
+fn generate_dataset_entries(mut result: RenderedScenery, mut entry: DatasetEntry) -> DatasetResult<()> {
+    drop_occluded_labels(&mut result, &entry);
+    save_dataset_entry(&result, &entry, &entry.name())?;
+
+    if entry.augmentations.is_empty() {
+        return Ok(());
+    }
+
+    let name = entry.augmented_name();
+    let applied = entry.augmentations.apply(&mut result, &mut entry.rng);
+
+    let record = std::fs::File::create(format!("{}/{name}.json", entry.augmentations_dir))?;
+    serde_json::to_writer_pretty(record, &applied)?;
+
+    // the augmentations can hide more of the buildings
+    drop_occluded_labels(&mut result, &entry);
+    save_dataset_entry(&result, &entry, &name)
+}
+
+fn drop_occluded_labels(result: &mut RenderedScenery, entry: &DatasetEntry) {
+    if entry.occluded == OccludedLabels::Drop {
+        result.labels.retain(|label| label.visibility >= entry.min_visibility);
+    }
+}
+
+fn tile_name(name: &str, x: u32, y: u32) -> String {
+    format!("{name}_tile_{x}_{y}")
+}
+
+/// saves the image, or each of its tiles, and writes the labels
+fn save_dataset_entry(result: &RenderedScenery, entry: &DatasetEntry, name: &str) -> DatasetResult<()> {
+    let Some(tiling) = entry.tiling else {
+        return save_image(result, entry, name);
+    };
+
+    for (x, y, tile) in tiling.split(result) {
+        save_image(&tile, entry, &tile_name(name, x, y))?;
+    }
+    Ok(())
+}
+
+/// saves the image and writes its labels in every requested format
+fn save_image(result: &RenderedScenery, entry: &DatasetEntry, name: &str) -> DatasetResult<()> {
+    let file_name = format!("{name}.png");
+
+    result
+        .image
+        .save(format!("{}/{file_name}", entry.image_dir))?;
+
+    let image = LabeledImage {
+        name,
+        file_name: &file_name,
+        width: result.image.width(),
+        height: result.image.height(),
+        instances: &result.instances,
+        min_visibility: entry.min_visibility,
+    };
+
+    for writer in entry.label_writers.iter() {
+        writer.write(entry.labels_dir.as_ref(), &image, &result.labels)?;
+    }
+    Ok(())
+}
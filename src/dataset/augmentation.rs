@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::cli::AugmentationArgs;
+use crate::dataset::overlay::UiOverlay;
+use crate::label::{LabelOptions, WallLabels};
+use crate::render::RenderedScenery;
+use crate::seed::SeededRng;
+use crate::validation::{MAX_TOWN_HALL_LEVEL, max_wall_level};
+use crate::wall::WallConnectionType;
+
+/// labels keeping less than this fraction of their box after a crop are dropped
+const MIN_CROP_COVERAGE: f32 = 0.5;
+
+pub trait Augmentation: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// applies the augmentation with random parameters, and returns them
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value;
+}
+
+/// An augmentation applied to an image, with the parameters it was applied with
+#[derive(Serialize)]
+pub struct AppliedAugmentation {
+    pub name: &'static str,
+    pub params: Value,
+}
+
+/// Augmentations applied in order between the render and the save of the images,
+/// each one with its own probability
+pub struct AugmentationPipeline {
+    steps: Vec<(f32, Box<dyn Augmentation>)>,
+}
+
+impl AugmentationPipeline {
+    pub fn from_args(args: &AugmentationArgs, label_options: LabelOptions) -> Self {
+        // geometric augmentations first, then the interface that is not affected by them,
+        // the JPEG compression must come last
+        let steps: Vec<(f32, Box<dyn Augmentation>)> = vec![
+            (args.scale_crop_probability, Box::new(ScaleCrop)),
+            (
+                args.flip_probability,
+                Box::new(HorizontalFlip {
+                    mirrored_classes: mirrored_wall_classes(label_options),
+                }),
+            ),
+            (args.ui_overlay_probability, Box::new(UiOverlay { panels: args.ui_panels.clone() })),
+            (args.color_jitter_probability, Box::new(ColorJitter)),
+            (args.blur_probability, Box::new(GaussianBlur)),
+            (args.noise_probability, Box::new(GaussianNoise)),
+            (args.dots_probability, Box::new(RandomDots)),
+            (args.jpeg_probability, Box::new(JpegCompression)),
+        ];
+
+        Self {
+            steps: steps.into_iter().filter(|(probability, _)| *probability > 0.0).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Vec<AppliedAugmentation> {
+        let mut applied = Vec::new();
+
+        for (probability, augmentation) in &self.steps {
+            if !rng.gen_bool(*probability as f64) {
+                continue;
+            }
+
+            applied.push(AppliedAugmentation {
+                name: augmentation.name(),
+                params: augmentation.apply(scenery, rng),
+            });
+        }
+
+        applied
+    }
+}
+
+struct ColorJitter;
+
+impl Augmentation for ColorJitter {
+    fn name(&self) -> &'static str {
+        "color_jitter"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let brightness = rng.gen_range(0.75f32..1.25);
+        let contrast = rng.gen_range(0.75f32..1.25);
+        let saturation = rng.gen_range(0.75f32..1.25);
+
+        for Rgba([r, g, b, _]) in scenery.image.pixels_mut() {
+            let luma = 0.299 * *r as f32 + 0.587 * *g as f32 + 0.114 * *b as f32;
+
+            for channel in [r, g, b] {
+                let value = *channel as f32;
+                let value = luma + (value - luma) * saturation;
+                let value = 128.0 + (value - 128.0) * contrast;
+                *channel = (value * brightness).clamp(0.0, 255.0) as u8;
+            }
+        }
+
+        json!({ "brightness": brightness, "contrast": contrast, "saturation": saturation })
+    }
+}
+
+struct GaussianBlur;
+
+impl Augmentation for GaussianBlur {
+    fn name(&self) -> &'static str {
+        "gaussian_blur"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let sigma = rng.gen_range(0.5f32..2.0);
+
+        scenery.image = imageproc::filter::gaussian_blur_f32(&scenery.image, sigma);
+
+        json!({ "sigma": sigma })
+    }
+}
+
+struct GaussianNoise;
+
+impl Augmentation for GaussianNoise {
+    fn name(&self) -> &'static str {
+        "gaussian_noise"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let stddev = rng.gen_range(2.0f64..12.0);
+        let seed: u64 = rng.gen();
+
+        imageproc::noise::gaussian_noise_mut(&mut scenery.image, 0.0, stddev, seed);
+        // the noise is also applied on the alpha channel
+        for pixel in scenery.image.pixels_mut() {
+            pixel.0[3] = 255;
+        }
+
+        json!({ "stddev": stddev, "seed": seed })
+    }
+}
+
+struct JpegCompression;
+
+impl Augmentation for JpegCompression {
+    fn name(&self) -> &'static str {
+        "jpeg_compression"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let quality = rng.gen_range(30u8..=90);
+
+        let rgb = DynamicImage::ImageRgba8(scenery.image.clone()).into_rgb8();
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, quality)
+            .encode_image(&rgb)
+            .unwrap();
+
+        scenery.image = image::load(Cursor::new(jpeg), image::ImageFormat::Jpeg)
+            .unwrap()
+            .into_rgba8();
+
+        json!({ "quality": quality })
+    }
+}
+
+/// zooms in the image and crops it back to its original size
+struct ScaleCrop;
+
+impl Augmentation for ScaleCrop {
+    fn name(&self) -> &'static str {
+        "scale_crop"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let scale = rng.gen_range(1.0f32..1.3);
+
+        let width = scenery.image.width();
+        let height = scenery.image.height();
+        let scaled_width = (width as f32 * scale) as u32;
+        let scaled_height = (height as f32 * scale) as u32;
+
+        let x = rng.gen_range(0..=scaled_width - width);
+        let y = rng.gen_range(0..=scaled_height - height);
+
+        let image = image::imageops::resize(&scenery.image, scaled_width, scaled_height, FilterType::Triangle);
+        let instances = image::imageops::resize(&scenery.instances, scaled_width, scaled_height, FilterType::Nearest);
+
+        scenery.image = image.view(x, y, width, height).to_image();
+        scenery.instances = instances.view(x, y, width, height).to_image();
+
+        // the normalized bounds are not changed by the scale, only by the crop
+        let crop = (x as f32, y as f32, width as f32, height as f32);
+        scenery.labels = scenery
+            .labels
+            .iter()
+            .filter_map(|label| {
+                label.crop(
+                    (scaled_width as f32, scaled_height as f32),
+                    crop,
+                    MIN_CROP_COVERAGE,
+                )
+            })
+            .collect();
+
+        json!({ "scale": scale, "x": x, "y": y })
+    }
+}
+
+struct HorizontalFlip {
+    /// class of each label class once mirrored, the classes missing from it are symmetrical
+    mirrored_classes: HashMap<usize, usize>,
+}
+
+impl Augmentation for HorizontalFlip {
+    fn name(&self) -> &'static str {
+        "horizontal_flip"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, _rng: &mut SeededRng) -> Value {
+        image::imageops::flip_horizontal_in_place(&mut scenery.image);
+        image::imageops::flip_horizontal_in_place(&mut scenery.instances);
+
+        for label in &mut scenery.labels {
+            label.bounds.x_center = 1.0 - label.bounds.x_center;
+            if let Some(class) = self.mirrored_classes.get(&label.class) {
+                label.class = *class;
+            }
+        }
+
+        json!({})
+    }
+}
+
+/// a wall connected on its left looks connected on its right once mirrored, and the other way around
+fn mirrored_wall_classes(label_options: LabelOptions) -> HashMap<usize, usize> {
+    let mut classes = HashMap::new();
+    if label_options.walls != WallLabels::PerWall {
+        return classes;
+    }
+
+    let taxonomy = label_options.taxonomy;
+    for level in 1..=max_wall_level(MAX_TOWN_HALL_LEVEL) {
+        let left = taxonomy.named_class_id(&taxonomy.wall_class_name(level, WallConnectionType::LeftOnly));
+        let right = taxonomy.named_class_id(&taxonomy.wall_class_name(level, WallConnectionType::RightOnly));
+        if left != right {
+            classes.insert(left, right);
+            classes.insert(right, left);
+        }
+    }
+
+    classes
+}
+
+/// white dots drawn over the image
+struct RandomDots;
+
+impl Augmentation for RandomDots {
+    fn name(&self) -> &'static str {
+        "random_dots"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        const DOTS_COUNT: u16 = 450;
+        const MIN_SIZE: u32 = 2;
+        const MAX_SIZE: u32 = 5;
+
+        let mut dots = Vec::new();
+
+        for _ in 0..DOTS_COUNT {
+            let radius = rng.gen_range(MIN_SIZE..=MAX_SIZE);
+            let x = rng.gen_range(0..scenery.image.width() - radius) as i32;
+            let y = rng.gen_range(0..scenery.image.height() - radius) as i32;
+
+            imageproc::drawing::draw_filled_circle_mut(
+                &mut scenery.image,
+                (x, y),
+                radius as i32,
+                Rgba([255, 255, 255, 255]),
+            );
+            dots.push(json!({ "x": x, "y": y, "radius": radius }));
+        }
+
+        json!({ "dots": dots })
+    }
+}
@@ -0,0 +1,185 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinSet;
+
+use crate::dataset::DatasetEntry;
+use crate::generator::GenerationError;
+
+/// Error of the generation of a single dataset entry, the other entries are still generated
+#[derive(Debug)]
+pub enum DatasetError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Json(serde_json::Error),
+    Generation(GenerationError),
+    Render(String),
+    /// the job panicked, with the panic message
+    Panic(String),
+}
+
+impl Display for DatasetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatasetError::Io(err) => write!(f, "{err}"),
+            DatasetError::Image(err) => write!(f, "{err}"),
+            DatasetError::Json(err) => write!(f, "{err}"),
+            DatasetError::Generation(err) => write!(f, "could not generate the village: {err}"),
+            DatasetError::Render(err) => write!(f, "could not render the image: {err}"),
+            DatasetError::Panic(message) => write!(f, "panicked: {message}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for DatasetError {
+    fn from(value: std::io::Error) -> Self {
+        DatasetError::Io(value)
+    }
+}
+
+impl From<image::ImageError> for DatasetError {
+    fn from(value: image::ImageError) -> Self {
+        DatasetError::Image(value)
+    }
+}
+
+impl From<serde_json::Error> for DatasetError {
+    fn from(value: serde_json::Error) -> Self {
+        DatasetError::Json(value)
+    }
+}
+
+impl From<GenerationError> for DatasetError {
+    fn from(value: GenerationError) -> Self {
+        DatasetError::Generation(value)
+    }
+}
+
+pub type DatasetResult<R> = Result<R, DatasetError>;
+
+/// Outcome of a run of the dataset jobs
+pub struct JobsReport {
+    pub generated: usize,
+    pub failed: usize,
+}
+
+/// Runs the jobs on the blocking thread pool, with at most `workers` jobs in flight.
+/// A failing or panicking job is reported and does not stop the others
+pub async fn run_jobs<F>(
+    entries: Vec<DatasetEntry>,
+    workers: usize,
+    skipped: usize,
+    generate_entries: F,
+) -> JobsReport
+where
+    F: Fn(DatasetEntry) -> DatasetResult<()> + Send + Sync + 'static,
+{
+    let generate_entries = Arc::new(generate_entries);
+    let mut progress = Progress::new(entries.len(), skipped);
+
+    let mut entries = entries.into_iter();
+    let mut set = JoinSet::new();
+
+    loop {
+        // the jobs are CPU bound, only a few of them are spawned at a time
+        while set.len() < workers.max(1) {
+            let Some(entry) = entries.next() else {
+                break;
+            };
+
+            let generate_entries = generate_entries.clone();
+            let job = format!("{}/{}", entry.split, entry.name());
+
+            set.spawn_blocking(move || {
+                let result = std::panic::catch_unwind(AssertUnwindSafe(|| generate_entries(entry)))
+                    .unwrap_or_else(|panic| Err(DatasetError::Panic(panic_message(panic))));
+                (job, result)
+            });
+        }
+
+        let Some(res) = set.join_next().await else {
+            break;
+        };
+        let (job, result) = res.expect("dataset jobs are never cancelled");
+
+        if let Err(err) = result {
+            progress.clear();
+            eprintln!("{job} failed: {err}");
+            progress.failed += 1;
+        } else {
+            progress.generated += 1;
+        }
+
+        progress.print();
+    }
+    println!();
+
+    JobsReport {
+        generated: progress.generated,
+        failed: progress.failed,
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+struct Progress {
+    /// jobs of this run, without the entries skipped on resume
+    total: usize,
+    skipped: usize,
+    generated: usize,
+    failed: usize,
+    start: Instant,
+}
+
+impl Progress {
+    fn new(total: usize, skipped: usize) -> Self {
+        Self {
+            total,
+            skipped,
+            generated: 0,
+            failed: 0,
+            start: Instant::now(),
+        }
+    }
+
+    fn print(&self) {
+        let done = self.generated + self.failed;
+        let elapsed = self.start.elapsed();
+        // the jobs all cost about the same, the remaining ones will take as long as the finished ones
+        let eta = elapsed.mul_f64((self.total - done) as f64 / done as f64);
+
+        self.clear();
+        print!(
+            "progress : {}/{}, {} failed, elapsed {}, eta {}",
+            done + self.skipped,
+            self.total + self.skipped,
+            self.failed,
+            format_duration(elapsed),
+            format_duration(eta),
+        );
+        std::io::stdout().flush().unwrap();
+    }
+
+    /// erases the progress line before something else is printed
+    fn clear(&self) {
+        print!("\r\x1b[2K");
+        std::io::stdout().flush().unwrap();
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
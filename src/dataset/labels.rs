@@ -0,0 +1,372 @@
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use image::{Pixel, Rgba};
+use serde::{Deserialize, Serialize};
+
+use crate::class_registry::Taxonomy;
+use crate::label::{Bounds, Label};
+use crate::render::instance::{BACKGROUND_INSTANCE, instance_polygon, InstanceMap};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+pub enum LabelFormat {
+    /// one `class x y w h` txt file per image
+    Yolo,
+    /// one `class x1 y1 x2 y2 ...` txt file per image, with the outline of the visible part of each instance
+    YoloSeg,
+    /// one 16 bits png per image, where the pixels of the nth label are set to n
+    InstanceMap,
+    /// one json file per split, in the `annotations` directory
+    Coco,
+    /// one Pascal VOC xml file per image
+    Voc,
+}
+
+impl LabelFormat {
+    pub fn writer(self, class_names: Arc<Vec<String>>, taxonomy: Taxonomy) -> Box<dyn LabelWriter> {
+        match self {
+            LabelFormat::Yolo => Box::new(YoloWriter),
+            LabelFormat::YoloSeg => Box::new(YoloSegWriter),
+            LabelFormat::InstanceMap => Box::new(InstanceMapWriter),
+            LabelFormat::Coco => Box::new(CocoWriter { class_names, taxonomy }),
+            LabelFormat::Voc => Box::new(VocWriter { class_names }),
+        }
+    }
+}
+
+/// What happens to the labels of the instances under the minimum visibility
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+pub enum OccludedLabels {
+    /// the labels are not written
+    Drop,
+    /// the labels are marked as difficult in VOC and occluded in COCO, other formats keep them as is
+    Flag,
+}
+
+/// The image the labels are written for
+pub struct LabeledImage<'a> {
+    /// file name without extension, shared by the image and its labels files
+    pub name: &'a str,
+    pub file_name: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub instances: &'a InstanceMap,
+    /// labels under this visibility are flagged as occluded
+    pub min_visibility: f32,
+}
+
+impl LabeledImage<'_> {
+    fn is_occluded(&self, label: &Label) -> bool {
+        label.visibility < self.min_visibility
+    }
+}
+
+pub trait LabelWriter: Send + Sync {
+    /// file holding the labels of the named image
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf;
+
+    /// writes the labels of one image in the labels directory of its split
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()>;
+
+    /// called once every image of the split got its labels written
+    fn finish_split(&self, _output: &Path, _split: &str, _labels_dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// bounds in pixels, as (x_min, y_min, width, height)
+fn pixel_box(bounds: &Bounds, image: &LabeledImage) -> (f32, f32, f32, f32) {
+    let width = bounds.width * image.width as f32;
+    let height = bounds.height * image.height as f32;
+    let x_min = bounds.x_center * image.width as f32 - width / 2.0;
+    let y_min = bounds.y_center * image.height as f32 - height / 2.0;
+
+    (x_min, y_min, width, height)
+}
+
+struct YoloWriter;
+
+impl LabelWriter for YoloWriter {
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf {
+        labels_dir.join(format!("{name}.txt"))
+    }
+
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()> {
+        let mut content = String::new();
+
+        for label in labels {
+            let Bounds {
+                x_center,
+                y_center,
+                width,
+                height,
+            } = label.bounds;
+            writeln!(content, "{} {x_center} {y_center} {width} {height}", label.class).unwrap();
+        }
+
+        std::fs::write(self.label_file(labels_dir, image.name), content)
+    }
+}
+
+struct YoloSegWriter;
+
+impl LabelWriter for YoloSegWriter {
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf {
+        labels_dir.join(format!("{name}.txt"))
+    }
+
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()> {
+        let mut content = String::new();
+
+        for label in labels {
+            let (x, y, width, height) = pixel_box(&label.bounds, image);
+            let area = (
+                x.max(0.0) as u32,
+                y.max(0.0) as u32,
+                (x + width).max(0.0) as u32,
+                (y + height).max(0.0) as u32,
+            );
+
+            // instances fully hidden by the sprites drawn over them have no outline
+            let Some(polygon) = instance_polygon(image.instances, label.instance, area) else {
+                continue;
+            };
+
+            write!(content, "{}", label.class).unwrap();
+            for (x, y) in polygon {
+                write!(
+                    content,
+                    " {} {}",
+                    x as f32 / image.width as f32,
+                    y as f32 / image.height as f32
+                )
+                .unwrap();
+            }
+            writeln!(content).unwrap();
+        }
+
+        std::fs::write(self.label_file(labels_dir, image.name), content)
+    }
+}
+
+struct InstanceMapWriter;
+
+impl LabelWriter for InstanceMapWriter {
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf {
+        labels_dir.join(format!("{name}.png"))
+    }
+
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()> {
+        // renumbers the instances in the order of the labels, dropping the unlabeled ones
+        let max_instance = labels.iter().map(|label| label.instance).max().unwrap_or(0);
+        let mut renumbering = vec![BACKGROUND_INSTANCE; max_instance as usize + 1];
+        for (idx, label) in labels.iter().enumerate() {
+            renumbering[label.instance as usize] = idx as u16 + 1;
+        }
+
+        let mut instances = image.instances.clone();
+        for pixel in instances.pixels_mut() {
+            pixel.0[0] = renumbering.get(pixel.0[0] as usize).copied().unwrap_or(BACKGROUND_INSTANCE);
+        }
+
+        instances
+            .save(self.label_file(labels_dir, image.name))
+            .map_err(io::Error::other)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoImage {
+    id: usize,
+    file_name: String,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoAnnotation {
+    id: usize,
+    image_id: usize,
+    category_id: usize,
+    bbox: [f32; 4],
+    area: f32,
+    iscrowd: u8,
+    attributes: CocoAttributes,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoAttributes {
+    occluded: bool,
+    visibility: f32,
+}
+
+#[derive(Serialize)]
+struct CocoCategory<'a> {
+    id: usize,
+    name: &'a str,
+    supercategory: &'a str,
+}
+
+/// labels of a single image, merged into the split's annotations file once the split is complete
+#[derive(Serialize, Deserialize)]
+struct CocoFragment {
+    image: CocoImage,
+    annotations: Vec<CocoAnnotation>,
+}
+
+#[derive(Serialize)]
+struct CocoDataset<'a> {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory<'a>>,
+}
+
+struct CocoWriter {
+    class_names: Arc<Vec<String>>,
+    taxonomy: Taxonomy,
+}
+
+impl LabelWriter for CocoWriter {
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf {
+        labels_dir.join(format!("{name}.json"))
+    }
+
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()> {
+        let annotations = labels
+            .iter()
+            .map(|label| {
+                let (x, y, width, height) = pixel_box(&label.bounds, image);
+                CocoAnnotation {
+                    id: 0,
+                    image_id: 0,
+                    category_id: label.class + 1,
+                    bbox: [x, y, width, height],
+                    area: width * height,
+                    iscrowd: 0,
+                    attributes: CocoAttributes {
+                        occluded: image.is_occluded(label),
+                        visibility: label.visibility,
+                    },
+                }
+            })
+            .collect();
+
+        let fragment = CocoFragment {
+            image: CocoImage {
+                id: 0,
+                file_name: image.file_name.to_string(),
+                width: image.width,
+                height: image.height,
+            },
+            annotations,
+        };
+
+        let file = std::fs::File::create(self.label_file(labels_dir, image.name))?;
+        serde_json::to_writer(file, &fragment).map_err(io::Error::from)
+    }
+
+    fn finish_split(&self, output: &Path, split: &str, labels_dir: &Path) -> io::Result<()> {
+        let mut fragment_files = std::fs::read_dir(labels_dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        fragment_files.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        fragment_files.sort();
+
+        // COCO ids start at 1
+        let mut dataset = CocoDataset {
+            images: Vec::new(),
+            annotations: Vec::new(),
+            categories: self
+                .class_names
+                .iter()
+                .enumerate()
+                .map(|(class, name)| CocoCategory {
+                    id: class + 1,
+                    name,
+                    supercategory: self.taxonomy.component_kind(name),
+                })
+                .collect(),
+        };
+
+        for (image_idx, path) in fragment_files.iter().enumerate() {
+            let content = std::fs::read_to_string(path)?;
+            let mut fragment: CocoFragment = serde_json::from_str(&content).map_err(io::Error::from)?;
+
+            fragment.image.id = image_idx + 1;
+            for mut annotation in fragment.annotations {
+                annotation.id = dataset.annotations.len() + 1;
+                annotation.image_id = fragment.image.id;
+                dataset.annotations.push(annotation);
+            }
+            dataset.images.push(fragment.image);
+        }
+
+        let annotations_dir = output.join("annotations");
+        std::fs::create_dir_all(&annotations_dir)?;
+
+        let file = std::fs::File::create(annotations_dir.join(format!("instances_{split}.json")))?;
+        serde_json::to_writer(file, &dataset).map_err(io::Error::from)
+    }
+}
+
+struct VocWriter {
+    class_names: Arc<Vec<String>>,
+}
+
+impl LabelWriter for VocWriter {
+    fn label_file(&self, labels_dir: &Path, name: &str) -> PathBuf {
+        labels_dir.join(format!("{name}.xml"))
+    }
+
+    fn write(&self, labels_dir: &Path, image: &LabeledImage, labels: &[Label]) -> io::Result<()> {
+        let mut xml = String::new();
+
+        writeln!(xml, "<annotation>").unwrap();
+        writeln!(xml, "  <filename>{}</filename>", escape_xml(image.file_name)).unwrap();
+        writeln!(xml, "  <size>").unwrap();
+        writeln!(xml, "    <width>{}</width>", image.width).unwrap();
+        writeln!(xml, "    <height>{}</height>", image.height).unwrap();
+        // the images are saved as rgba pngs
+        writeln!(xml, "    <depth>{}</depth>", Rgba::<u8>::CHANNEL_COUNT).unwrap();
+        writeln!(xml, "  </size>").unwrap();
+        writeln!(xml, "  <segmented>0</segmented>").unwrap();
+
+        for label in labels {
+            let (x, y, width, height) = pixel_box(&label.bounds, image);
+
+            // VOC boxes are inclusive pixel coordinates clamped to the image
+            let x_min = x.round().clamp(0.0, image.width as f32 - 1.0) as u32;
+            let y_min = y.round().clamp(0.0, image.height as f32 - 1.0) as u32;
+            let x_max = (x + width).round().clamp(0.0, image.width as f32 - 1.0) as u32;
+            let y_max = (y + height).round().clamp(0.0, image.height as f32 - 1.0) as u32;
+
+            writeln!(xml, "  <object>").unwrap();
+            writeln!(xml, "    <name>{}</name>", escape_xml(&self.class_names[label.class])).unwrap();
+            writeln!(xml, "    <pose>Unspecified</pose>").unwrap();
+            writeln!(xml, "    <truncated>0</truncated>").unwrap();
+            writeln!(xml, "    <difficult>{}</difficult>", image.is_occluded(label) as u8).unwrap();
+            writeln!(xml, "    <bndbox>").unwrap();
+            writeln!(xml, "      <xmin>{x_min}</xmin>").unwrap();
+            writeln!(xml, "      <ymin>{y_min}</ymin>").unwrap();
+            writeln!(xml, "      <xmax>{x_max}</xmax>").unwrap();
+            writeln!(xml, "      <ymax>{y_max}</ymax>").unwrap();
+            writeln!(xml, "    </bndbox>").unwrap();
+            writeln!(xml, "  </object>").unwrap();
+        }
+
+        writeln!(xml, "</annotation>").unwrap();
+
+        std::fs::write(self.label_file(labels_dir, image.name), xml)
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
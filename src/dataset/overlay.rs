@@ -0,0 +1,240 @@
+use ab_glyph::FontRef;
+use clap::ValueEnum;
+use image::{Luma, Rgba};
+use imageproc::drawing;
+use imageproc::rect::Rect;
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::dataset::augmentation::Augmentation;
+use crate::label::Bounds;
+use crate::render::instance::{BACKGROUND_INSTANCE, InstanceMap};
+use crate::render::{cache, Image, RenderedScenery};
+use crate::seed::SeededRng;
+
+const PANEL_COLOR: Rgba<u8> = Rgba([40, 40, 48, 255]);
+const BORDER_COLOR: Rgba<u8> = Rgba([10, 10, 12, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Element of the in-game interface drawn over the village
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum, Serialize)]
+pub enum UiPanel {
+    /// level badge, name and trophies, in the upper left corner
+    PlayerInfo,
+    /// number of free builders, at the top
+    BuilderBanner,
+    /// gold, elixir and dark elixir bars, in the upper right corner
+    ResourceBars,
+    /// attack button, in the lower left corner
+    AttackButton,
+    /// shop button, in the lower right corner
+    ShopButton,
+    /// chat tab on the left side, sometimes opened
+    Chat,
+}
+
+/// A rectangle of the overlay, in pixels
+#[derive(Serialize)]
+struct DrawnRect {
+    panel: UiPanel,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// Draws the selected panels of the game interface over the render.
+/// The pixels under the panels are removed from the instance map, the labels lose the visibility of the hidden
+/// pixels and are shrunk to what is left visible
+pub struct UiOverlay {
+    pub panels: Vec<UiPanel>,
+}
+
+impl Augmentation for UiOverlay {
+    fn name(&self) -> &'static str {
+        "ui_overlay"
+    }
+
+    fn apply(&self, scenery: &mut RenderedScenery, rng: &mut SeededRng) -> Value {
+        let pixels_before = instance_pixels(&scenery.instances);
+
+        let mut overlay = Overlay {
+            image: &mut scenery.image,
+            instances: &mut scenery.instances,
+            font: cache::font(),
+            rects: Vec::new(),
+        };
+
+        for panel in &self.panels {
+            overlay.draw(*panel, rng);
+        }
+
+        let rects = overlay.rects;
+        let pixels_after = instance_pixels(&scenery.instances);
+
+        // the labels are only made less visible, the occluded ones are dropped or flagged with the others
+        for label in &mut scenery.labels {
+            let before = pixels_before.get(label.instance as usize).copied().unwrap_or(0);
+            let after = pixels_after.get(label.instance as usize).copied().unwrap_or(0);
+            if before == 0 || after == before {
+                continue;
+            }
+
+            label.visibility *= after as f32 / before as f32;
+
+            // the box is shrunk to the pixels left visible by the overlay
+            let Some(extent) = instance_extent(&scenery.instances, label.instance) else {
+                continue;
+            };
+            let visible = Bounds::from_extent(extent, scenery.image.width(), scenery.image.height());
+
+            if let Some(bounds) = label.bounds.intersection(&visible) {
+                label.bounds = bounds;
+            }
+        }
+
+        serde_json::to_value(rects).unwrap()
+    }
+}
+
+struct Overlay<'a> {
+    image: &'a mut Image,
+    instances: &'a mut InstanceMap,
+    font: &'static FontRef<'static>,
+    rects: Vec<DrawnRect>,
+}
+
+impl Overlay<'_> {
+    fn draw(&mut self, panel: UiPanel, rng: &mut SeededRng) {
+        let unit = self.image.width() as f32 / 100.0;
+        let width = self.image.width() as f32;
+        let height = self.image.height() as f32;
+
+        // the position of the interface varies a little between devices
+        let mut jitter = |value: f32| value + rng.gen_range(-0.5f32..0.5) * unit;
+
+        match panel {
+            UiPanel::PlayerInfo => {
+                let (x, y) = (jitter(unit), jitter(unit));
+                self.panel(panel, x, y, 5.0 * unit, 5.0 * unit, Rgba([50, 110, 200, 255]));
+                self.panel(panel, x + 5.5 * unit, y + 0.5 * unit, 16.0 * unit, 4.0 * unit, PANEL_COLOR);
+                let name = format!("Chief{}", rng.gen_range(100..10000));
+                self.text(x + 6.0 * unit, y + unit, 2.5 * unit, &name);
+                let trophies = rng.gen_range(0..6000).to_string();
+                self.text(x + 15.0 * unit, y + unit, 2.5 * unit, &trophies);
+            }
+            UiPanel::BuilderBanner => {
+                let (x, y) = (jitter(width / 2.0 - 8.0 * unit), jitter(unit));
+                self.panel(panel, x, y, 16.0 * unit, 4.0 * unit, PANEL_COLOR);
+                let total = rng.gen_range(2..=6);
+                let text = format!("Builders: {}/{total}", rng.gen_range(0..=total));
+                self.text(x + unit, y + 0.7 * unit, 2.5 * unit, &text);
+            }
+            UiPanel::ResourceBars => {
+                let x = jitter(width - 23.0 * unit);
+                let y = jitter(unit);
+                let colors = [
+                    Rgba([240, 200, 40, 255]),
+                    Rgba([200, 60, 220, 255]),
+                    Rgba([30, 30, 40, 255]),
+                ];
+                for (idx, color) in colors.into_iter().enumerate() {
+                    let bar_y = y + idx as f32 * 4.5 * unit;
+                    self.panel(panel, x, bar_y, 20.0 * unit, 3.5 * unit, PANEL_COLOR);
+                    let fill = rng.gen_range(0.05f32..1.0);
+                    self.fill(x, bar_y, 20.0 * unit * fill, 3.5 * unit, color);
+                    let amount = rng.gen_range(0..20_000_000).to_string();
+                    self.text(x + unit, bar_y + 0.4 * unit, 2.5 * unit, &amount);
+                }
+            }
+            UiPanel::AttackButton => {
+                let side = 10.0 * unit;
+                let (x, y) = (jitter(unit), jitter(height - side - unit));
+                self.panel(panel, x, y, side, side, Rgba([230, 140, 30, 255]));
+                self.text(x + unit, y + side - 3.5 * unit, 2.8 * unit, "Attack!");
+            }
+            UiPanel::ShopButton => {
+                let side = 10.0 * unit;
+                let (x, y) = (jitter(width - side - unit), jitter(height - side - unit));
+                self.panel(panel, x, y, side, side, Rgba([70, 170, 60, 255]));
+                self.text(x + 2.5 * unit, y + side - 3.5 * unit, 2.8 * unit, "Shop");
+            }
+            UiPanel::Chat => {
+                let y = jitter(height * 0.4);
+                if rng.gen_bool(0.3) {
+                    let (panel_width, panel_height) = (30.0 * unit, height * 0.6);
+                    let y = y - panel_height / 2.0;
+                    self.panel(panel, 0.0, y, panel_width, panel_height, PANEL_COLOR);
+                    let lines = (panel_height / (4.0 * unit)) as usize;
+                    for line in 0..lines {
+                        let text = format!("Chief{}: gg", rng.gen_range(100..10000));
+                        self.text(unit, y + unit + line as f32 * 4.0 * unit, 2.2 * unit, &text);
+                    }
+                } else {
+                    self.panel(panel, 0.0, y, 2.5 * unit, 10.0 * unit, PANEL_COLOR);
+                }
+            }
+        }
+    }
+
+    /// an opaque rectangle hiding the village, with a border
+    fn panel(&mut self, panel: UiPanel, x: f32, y: f32, width: f32, height: f32, color: Rgba<u8>) {
+        let rect = Rect::at(x as i32, y as i32).of_size(width.max(1.0) as u32, height.max(1.0) as u32);
+
+        drawing::draw_filled_rect_mut(self.image, rect, color);
+        drawing::draw_hollow_rect_mut(self.image, rect, BORDER_COLOR);
+        drawing::draw_filled_rect_mut(self.instances, rect, Luma([BACKGROUND_INSTANCE]));
+
+        self.rects.push(DrawnRect {
+            panel,
+            x: rect.left(),
+            y: rect.top(),
+            width: rect.width(),
+            height: rect.height(),
+        });
+    }
+
+    /// a rectangle drawn inside a panel
+    fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgba<u8>) {
+        let rect = Rect::at(x as i32, y as i32).of_size(width.max(1.0) as u32, height.max(1.0) as u32);
+        drawing::draw_filled_rect_mut(self.image, rect, color);
+    }
+
+    fn text(&mut self, x: f32, y: f32, size: f32, text: &str) {
+        drawing::draw_text_mut(self.image, TEXT_COLOR, x as i32, y as i32, size, self.font, text);
+    }
+}
+
+/// number of pixels of each instance
+fn instance_pixels(instances: &InstanceMap) -> Vec<u32> {
+    let mut pixels = Vec::new();
+
+    for pixel in instances.pixels() {
+        let instance = pixel.0[0] as usize;
+        if instance >= pixels.len() {
+            pixels.resize(instance + 1, 0);
+        }
+        pixels[instance] += 1;
+    }
+
+    pixels
+}
+
+/// (x_min, y_min, x_max, y_max) extent of the pixels of an instance
+fn instance_extent(instances: &InstanceMap, instance: u16) -> Option<(u32, u32, u32, u32)> {
+    let mut extent: Option<(u32, u32, u32, u32)> = None;
+
+    for (x, y, pixel) in instances.enumerate_pixels() {
+        if pixel.0[0] != instance {
+            continue;
+        }
+
+        extent = Some(match extent {
+            None => (x, y, x, y),
+            Some((x_min, y_min, x_max, y_max)) => (x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y)),
+        });
+    }
+
+    extent
+}
@@ -0,0 +1,74 @@
+use image::GenericImageView;
+
+use crate::render::RenderedScenery;
+
+/// Cuts renders into overlapping square tiles
+#[derive(Copy, Clone)]
+pub struct Tiling {
+    /// side of the tiles, in pixels
+    pub size: u32,
+    /// fraction of a tile shared with its neighbours
+    pub overlap: f32,
+    /// labels keeping less than this fraction of their box in a tile are dropped from the tile
+    pub min_coverage: f32,
+}
+
+impl Tiling {
+    /// the tiles of the render, with the pixel position of their upper left corner
+    pub fn split(&self, scenery: &RenderedScenery) -> Vec<(u32, u32, RenderedScenery)> {
+        self.tile_origins(scenery.image.width(), scenery.image.height())
+            .into_iter()
+            .map(|(x, y)| (x, y, self.crop(scenery, x, y)))
+            .collect()
+    }
+
+    /// pixel position of the upper left corner of every tile of an image of the given size
+    pub fn tile_origins(&self, width: u32, height: u32) -> Vec<(u32, u32)> {
+        let mut origins = Vec::new();
+
+        for y in self.tile_starts(height) {
+            for x in self.tile_starts(width) {
+                origins.push((x, y));
+            }
+        }
+
+        origins
+    }
+
+    /// start of every tile along an axis, the last tile is aligned on the end of the axis
+    fn tile_starts(&self, length: u32) -> Vec<u32> {
+        if length <= self.size {
+            return vec![0];
+        }
+
+        let step = ((self.size as f32 * (1.0 - self.overlap)) as u32).max(1);
+        let last = length - self.size;
+
+        let mut starts = (0..last).step_by(step as usize).collect::<Vec<_>>();
+        starts.push(last);
+        starts
+    }
+
+    fn crop(&self, scenery: &RenderedScenery, x: u32, y: u32) -> RenderedScenery {
+        let width = self.size.min(scenery.image.width());
+        let height = self.size.min(scenery.image.height());
+
+        let labels = scenery
+            .labels
+            .iter()
+            .filter_map(|label| {
+                label.crop(
+                    (scenery.image.width() as f32, scenery.image.height() as f32),
+                    (x as f32, y as f32, width as f32, height as f32),
+                    self.min_coverage,
+                )
+            })
+            .collect();
+
+        RenderedScenery {
+            image: scenery.image.view(x, y, width, height).to_image(),
+            labels,
+            instances: scenery.instances.view(x, y, width, height).to_image(),
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::buidling::{Building, BuildingType};
+use crate::cell::Cell;
+use crate::validation::{max_building_count, max_building_level, max_wall_count, max_wall_level, validate_village};
+use crate::village::{Component, ComponentType, Village, VillageOperationError, VillageOperationResult};
+use crate::wall::Wall;
+
+/// interior side of a walled compartment, fits two 3x3 buildings separated by a lane
+const COMPARTMENT_SIZE: i16 = 7;
+const MAX_COMPARTMENTS_PER_SIDE: i16 = 4;
+
+#[derive(Debug)]
+pub enum GenerationError {
+    InvalidTownHallLevel(u8),
+    /// the village is too small for the town hall
+    TownHallDoesNotFit,
+    Village(VillageOperationError),
+}
+
+pub type GenerationResult<R> = Result<R, GenerationError>;
+
+impl Display for GenerationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerationError::InvalidTownHallLevel(level) => write!(
+                f,
+                "the town hall level must be in {:?}, got {level}",
+                BuildingType::TownHall.level_range()
+            ),
+            GenerationError::TownHallDoesNotFit => write!(f, "the town hall does not fit in the village"),
+            GenerationError::Village(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<VillageOperationError> for GenerationError {
+    fn from(value: VillageOperationError) -> Self {
+        GenerationError::Village(value)
+    }
+}
+
+/// Generates a legal village for the given town hall level.
+/// Buildings get correlated levels, defenses and storages are packed into walled
+/// compartments around the town hall while the other buildings are spread around the walls.
+pub fn generate_village(rng: &mut impl Rng, town_hall_level: u8) -> GenerationResult<Village> {
+    if !BuildingType::TownHall.level_range().contains(&town_hall_level) {
+        return Err(GenerationError::InvalidTownHallLevel(town_hall_level));
+    }
+
+    let mut village = Village::default();
+
+    // how far the player is in the upgrades of its town hall level
+    let progress = rng.gen_range(0.6f32..1.0);
+
+    let mut inside = Vec::new();
+    let mut outside = Vec::new();
+    let mut hidden = Vec::new();
+
+    let building_types = BuildingType::all()
+        .into_iter()
+        .map(|building_type| building_type.with_random_state(rng))
+        .collect::<Vec<_>>();
+    for building_type in building_types {
+        let count = match building_type {
+            BuildingType::TownHall => 0, // the town hall is placed first, in the center
+            _ => max_building_count(&building_type, town_hall_level),
+        };
+        // buildings of the same type tend to be upgraded together
+        let type_progress = progress + rng.gen_range(-0.1f32..0.05);
+
+        for _ in 0..count {
+            let Some(level) = pick_level(rng, &building_type, town_hall_level, type_progress) else {
+                break; // no sprite is available for the levels allowed by the town hall
+            };
+
+            let building = Building {
+                building_type: building_type.clone(),
+                level,
+                characteristics: building_type.characteristics(level),
+            };
+
+            if is_hidden(&building_type) {
+                hidden.push(building)
+            } else if is_kept_inside_walls(&building_type) {
+                inside.push(building)
+            } else {
+                outside.push(building)
+            }
+        }
+    }
+
+    // pack the biggest buildings first to keep the compartments compact
+    inside.shuffle(rng);
+    inside.sort_by_key(|b| std::cmp::Reverse(b.building_type.plot_size().cell_diameter()));
+
+    let compartments = Compartments::new(rng, &inside, &village);
+
+    let town_hall = Building {
+        building_type: BuildingType::TownHall,
+        level: town_hall_level,
+        characteristics: BuildingType::TownHall.characteristics(town_hall_level),
+    };
+    if !compartments.place(&mut village, town_hall)? {
+        return Err(GenerationError::TownHallDoesNotFit);
+    }
+
+    for building in inside {
+        if !compartments.place(&mut village, building.clone())? {
+            outside.push(building)
+        }
+    }
+
+    let wall_level = pick_wall_level(rng, town_hall_level, progress);
+    for cell in compartments.wall_cells(&village, max_wall_count(town_hall_level) as usize) {
+        village.add_component(cell, wall_component(wall_level))?;
+    }
+
+    outside.shuffle(rng);
+    outside.sort_by_key(|b| std::cmp::Reverse(b.building_type.plot_size().cell_diameter()));
+    scatter(rng, &mut village, &compartments, outside, false)?;
+    scatter(rng, &mut village, &compartments, hidden, true)?;
+
+    debug_assert!(validate_village(&village).is_empty());
+
+    Ok(village)
+}
+
+/// picks a level that is allowed by the town hall and that has a sprite
+fn pick_level(rng: &mut impl Rng, building_type: &BuildingType, town_hall_level: u8, progress: f32) -> Option<u8> {
+    let max_level = max_building_level(building_type, town_hall_level);
+    let sprites_range = building_type.level_range();
+
+    let min = *sprites_range.start();
+    let max = max_level.min(*sprites_range.end());
+
+    if min > max {
+        return None;
+    }
+
+    let level = (max_level as f32 * (progress + rng.gen_range(-0.05f32..0.05))).round() as u8;
+    Some(level.clamp(min, max))
+}
+
+fn pick_wall_level(rng: &mut impl Rng, town_hall_level: u8, progress: f32) -> u8 {
+    let max_level = max_wall_level(town_hall_level).max(1);
+    let level = (max_level as f32 * (progress + rng.gen_range(-0.1f32..0.0))).round() as u8;
+    level.clamp(1, max_level)
+}
+
+fn wall_component(level: u8) -> Component {
+    let wall = Wall { level };
+    Component {
+        life_points: Some(wall.life_points()),
+        kind: ComponentType::Wall(wall),
+    }
+}
+
+/// traps and hidden teslas, that are hidden in the compartments
+fn is_hidden(building_type: &BuildingType) -> bool {
+    building_type.is_trap() || matches!(building_type, BuildingType::HiddenTesla)
+}
+
+fn is_kept_inside_walls(building_type: &BuildingType) -> bool {
+    matches!(
+        building_type,
+        BuildingType::ArcherTower(_)
+            | BuildingType::Cannon(_)
+            | BuildingType::DarkElixirStorage
+            | BuildingType::EagleArtillery(_)
+            | BuildingType::ElixirStorage(_)
+            | BuildingType::GoldStorage(_)
+            | BuildingType::InfernoTower(_)
+            | BuildingType::Mortar(_)
+            | BuildingType::Scattershot(_)
+            | BuildingType::WizardTower
+    )
+}
+
+fn building_component(building: Building) -> Component {
+    Component {
+        life_points: building.building_type.life_points(building.level),
+        kind: ComponentType::Building(building),
+    }
+}
+
+/// returns true if the building can be placed at origin while keeping a free lane around it
+fn fits_with_lane(village: &Village, origin: Cell, diameter: i16, lane: i16) -> bool {
+    for x in -lane..diameter + lane {
+        for y in -lane..diameter + lane {
+            let cell = Cell::new(origin.x + x, origin.y + y);
+            let in_footprint = x >= 0 && y >= 0 && x < diameter && y < diameter;
+
+            match village.get_component_at(cell) {
+                Some(_) if in_footprint => return false,
+                Some((_, c)) if matches!(c.kind, ComponentType::Building(_)) => return false,
+                _ => {}
+            }
+        }
+    }
+
+    let plate_width = village.scenery().params().plate_width_cells as i16;
+    let plate_height = village.scenery().params().plate_height_cells as i16;
+
+    origin.x >= 0 && origin.y >= 0 && origin.x + diameter <= plate_width && origin.y + diameter <= plate_height
+}
+
+/// A square grid of walled compartments, centered on the plate.
+struct Compartments {
+    origin: Cell,
+    per_side: i16,
+}
+
+impl Compartments {
+    fn new(rng: &mut impl Rng, inside: &[Building], village: &Village) -> Self {
+        // the area of each building and its lanes, plus the town hall
+        let needed_area = inside
+            .iter()
+            .map(|b| (b.building_type.plot_size().cell_diameter() as i16 + 1).pow(2))
+            .sum::<i16>()
+            + 25;
+
+        let compartment_area = (COMPARTMENT_SIZE + 1).pow(2);
+        let compartments = (needed_area as f32 / compartment_area as f32 * 1.2).ceil();
+        let per_side = (compartments.sqrt().ceil() as i16).clamp(1, MAX_COMPARTMENTS_PER_SIDE);
+
+        let side = per_side * (COMPARTMENT_SIZE + 1) + 1;
+        let plate_width = village.scenery().params().plate_width_cells as i16;
+        let plate_height = village.scenery().params().plate_height_cells as i16;
+
+        let max_shift = 2;
+        let origin = Cell::new(
+            ((plate_width - side) / 2 + rng.gen_range(-max_shift..=max_shift)).clamp(0, plate_width - side),
+            ((plate_height - side) / 2 + rng.gen_range(-max_shift..=max_shift)).clamp(0, plate_height - side),
+        );
+
+        Self { origin, per_side }
+    }
+
+    fn side(&self) -> i16 {
+        self.per_side * (COMPARTMENT_SIZE + 1) + 1
+    }
+
+    fn center(&self) -> Cell {
+        Cell::new(self.origin.x + self.side() / 2, self.origin.y + self.side() / 2)
+    }
+
+    fn contains(&self, cell: Cell) -> bool {
+        cell.x >= self.origin.x
+            && cell.y >= self.origin.y
+            && cell.x < self.origin.x + self.side()
+            && cell.y < self.origin.y + self.side()
+    }
+
+    /// the first interior cell of each compartment, the closest to the center first
+    fn interiors(&self) -> Vec<Cell> {
+        let mut interiors = Vec::new();
+
+        for i in 0..self.per_side {
+            for j in 0..self.per_side {
+                interiors.push(Cell::new(
+                    self.origin.x + 1 + i * (COMPARTMENT_SIZE + 1),
+                    self.origin.y + 1 + j * (COMPARTMENT_SIZE + 1),
+                ));
+            }
+        }
+
+        let center = self.center();
+        let half = COMPARTMENT_SIZE / 2;
+        interiors.sort_by(|a, b| {
+            let a = Cell::new(a.x + half, a.y + half).distance(center);
+            let b = Cell::new(b.x + half, b.y + half).distance(center);
+            a.total_cmp(&b)
+        });
+
+        interiors
+    }
+
+    /// tries to place the building in the first compartment that can host it,
+    /// returns false if no compartment had enough room left
+    fn place(&self, village: &mut Village, building: Building) -> VillageOperationResult<bool> {
+        let diameter = building.building_type.plot_size().cell_diameter() as i16;
+
+        for interior in self.interiors() {
+            for x in 0..=COMPARTMENT_SIZE - diameter {
+                for y in 0..=COMPARTMENT_SIZE - diameter {
+                    let origin = Cell::new(interior.x + x, interior.y + y);
+                    if fits_with_lane(village, origin, diameter, 1) {
+                        village.add_component(origin, building_component(building))?;
+                        return Ok(true);
+                    }
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// the walls surrounding the compartments that host buildings, within the given budget.
+    /// The inner compartments are walled first
+    fn wall_cells(&self, village: &Village, budget: usize) -> Vec<Cell> {
+        let mut cells = BTreeSet::new();
+        let mut walls = Vec::new();
+
+        for interior in self.interiors() {
+            let is_used = (0..COMPARTMENT_SIZE).any(|x| {
+                (0..COMPARTMENT_SIZE).any(|y| {
+                    village
+                        .get_component_at(Cell::new(interior.x + x, interior.y + y))
+                        .is_some()
+                })
+            });
+            if !is_used {
+                continue;
+            }
+
+            let mut compartment_walls = Vec::new();
+            for i in -1..=COMPARTMENT_SIZE {
+                compartment_walls.push(Cell::new(interior.x + i, interior.y - 1));
+                compartment_walls.push(Cell::new(interior.x + i, interior.y + COMPARTMENT_SIZE));
+                compartment_walls.push(Cell::new(interior.x - 1, interior.y + i));
+                compartment_walls.push(Cell::new(interior.x + COMPARTMENT_SIZE, interior.y + i));
+            }
+            compartment_walls.retain(|c| !cells.contains(c) && village.get_component_at(*c).is_none());
+            compartment_walls.sort();
+            compartment_walls.dedup();
+
+            if walls.len() + compartment_walls.len() > budget {
+                break;
+            }
+
+            cells.extend(compartment_walls.iter().copied());
+            walls.extend(compartment_walls);
+        }
+
+        walls
+    }
+}
+
+/// places the buildings at random free positions, either inside the compartments or around them.
+/// Buildings placed around the compartments keep a lane around them
+fn scatter(
+    rng: &mut impl Rng,
+    village: &mut Village,
+    compartments: &Compartments,
+    buildings: Vec<Building>,
+    in_compartments: bool,
+) -> VillageOperationResult<()> {
+    let plate_width = village.scenery().params().plate_width_cells as i16;
+    let plate_height = village.scenery().params().plate_height_cells as i16;
+
+    let mut candidates = Vec::new();
+    for x in 0..plate_width {
+        for y in 0..plate_height {
+            candidates.push(Cell::new(x, y));
+        }
+    }
+    candidates.shuffle(rng);
+
+    // prefer the positions near the center to keep the village compact
+    let center = compartments.center();
+    candidates.sort_by_key(|c| (c.distance(center) / 3.0) as u16);
+
+    let lane = if in_compartments { 0 } else { 1 };
+
+    for building in buildings {
+        let diameter = building.building_type.plot_size().cell_diameter() as i16;
+
+        let origin = candidates.iter().copied().find(|origin| {
+            let overlaps_compartments = (0..diameter).any(|x| {
+                (0..diameter).any(|y| compartments.contains(Cell::new(origin.x + x, origin.y + y)))
+            });
+            overlaps_compartments == in_compartments && fits_with_lane(village, *origin, diameter, lane)
+        });
+
+        // there is no room left for this building, the village will simply miss it
+        if let Some(origin) = origin {
+            village.add_component(origin, building_component(building))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::attack_simulation::{simulate_attack, AttackPlan};
+    use crate::position::Pos;
+    use crate::seed::SeededRng;
+    use crate::troop::{Troop, TroopType};
+    use crate::village::ComponentType;
+
+    use super::{generate_village, GenerationError};
+
+    #[test]
+    fn generated_villages_can_be_attacked() {
+        let attack_plan = AttackPlan::new(vec![
+            Troop {
+                tpe: TroopType::Barbarian,
+                level: 4,
+                pos: Pos::new(0.0, 0.0),
+            },
+            Troop {
+                tpe: TroopType::Giant,
+                level: 3,
+                pos: Pos::new(44.0, 44.0),
+            },
+        ])
+        .unwrap();
+
+        for town_hall_level in [3, 9, 16] {
+            let mut rng = SeededRng::seed_from_u64(town_hall_level as u64);
+            let village = generate_village(&mut rng, town_hall_level).unwrap();
+
+            for (_, _, component) in village.iter_components() {
+                let is_trap = matches!(&component.kind, ComponentType::Building(b) if b.building_type.is_trap());
+                assert_eq!(component.life_points.is_some(), !is_trap);
+            }
+            assert!(village.state().remaining_defenses > 0);
+
+            simulate_attack(60, &village, &attack_plan);
+        }
+    }
+
+    #[test]
+    fn invalid_town_hall_levels_are_rejected() {
+        let mut rng = SeededRng::seed_from_u64(0);
+
+        for town_hall_level in [0, 17] {
+            let result = generate_village(&mut rng, town_hall_level);
+            assert!(matches!(result, Err(GenerationError::InvalidTownHallLevel(level)) if level == town_hall_level));
+        }
+    }
+}
@@ -1,4 +1,8 @@
-#[derive(Default)]
+use clap::ValueEnum;
+
+use crate::class_registry::Taxonomy;
+
+#[derive(Default, Clone, Debug)]
 pub struct Bounds {
     pub(crate) x_center: f32,
     pub(crate) y_center: f32,
@@ -6,7 +10,151 @@ pub struct Bounds {
     pub(crate) height: f32,
 }
 
+/// How the bounds of a rendered sprite are computed
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, ValueEnum)]
+pub enum BoundsMode {
+    /// extent of the non-transparent pixels of the sprite, clipped to the image
+    #[default]
+    Tight,
+    /// whole sprite rectangle, transparent padding included
+    Sprite,
+}
+
+/// Which labels are given to the walls
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, ValueEnum)]
+pub enum WallLabels {
+    /// walls are not labeled
+    #[default]
+    None,
+    /// one label per wall, with a class per wall level and connection type
+    PerWall,
+    /// one label per straight run of walls
+    Segments,
+}
+
+/// How the labels of a rendered image are computed
+#[derive(Copy, Clone, Default)]
+pub struct LabelOptions {
+    pub bounds: BoundsMode,
+    pub taxonomy: Taxonomy,
+    pub walls: WallLabels,
+}
+
+impl Bounds {
+    /// bounds of the inclusive pixel extent (x_min, y_min, x_max, y_max) of an image
+    pub fn from_extent(extent: (u32, u32, u32, u32), image_width: u32, image_height: u32) -> Self {
+        let (x_min, y_min, x_max, y_max) = extent;
+        let width = (x_max - x_min + 1) as f32;
+        let height = (y_max - y_min + 1) as f32;
+
+        Self {
+            x_center: (x_min as f32 + width / 2.0) / image_width as f32,
+            y_center: (y_min as f32 + height / 2.0) / image_height as f32,
+            width: width / image_width as f32,
+            height: height / image_height as f32,
+        }
+    }
+
+    /// smallest bounds containing both bounds
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let x_min = (self.x_center - self.width / 2.0).min(other.x_center - other.width / 2.0);
+        let y_min = (self.y_center - self.height / 2.0).min(other.y_center - other.height / 2.0);
+        let x_max = (self.x_center + self.width / 2.0).max(other.x_center + other.width / 2.0);
+        let y_max = (self.y_center + self.height / 2.0).max(other.y_center + other.height / 2.0);
+
+        Bounds {
+            x_center: (x_min + x_max) / 2.0,
+            y_center: (y_min + y_max) / 2.0,
+            width: x_max - x_min,
+            height: y_max - y_min,
+        }
+    }
+
+    /// bounds shared by both bounds, None if they do not overlap
+    pub fn intersection(&self, other: &Bounds) -> Option<Bounds> {
+        let x_min = (self.x_center - self.width / 2.0).max(other.x_center - other.width / 2.0);
+        let y_min = (self.y_center - self.height / 2.0).max(other.y_center - other.height / 2.0);
+        let x_max = (self.x_center + self.width / 2.0).min(other.x_center + other.width / 2.0);
+        let y_max = (self.y_center + self.height / 2.0).min(other.y_center + other.height / 2.0);
+
+        if x_min >= x_max || y_min >= y_max {
+            return None;
+        }
+
+        Some(Bounds {
+            x_center: (x_min + x_max) / 2.0,
+            y_center: (y_min + y_max) / 2.0,
+            width: x_max - x_min,
+            height: y_max - y_min,
+        })
+    }
+}
+
+/// Bounds of a sprite drawn on an image
+pub struct SpriteBounds {
+    pub sprite: Bounds,
+    /// None if no opaque pixel of the sprite is in the image
+    pub opaque: Option<Bounds>,
+    /// opaque pixels of the sprite, including the ones out of the image
+    pub opaque_pixels: u32,
+}
+
+impl SpriteBounds {
+    pub fn bounds(&self, mode: BoundsMode) -> Option<Bounds> {
+        match mode {
+            BoundsMode::Tight => self.opaque.clone(),
+            BoundsMode::Sprite => Some(self.sprite.clone()),
+        }
+    }
+}
+
 pub struct Label {
     pub class: usize,
     pub bounds: Bounds,
+    /// value of the labeled sprite pixels in the instance map of the rendered image
+    pub instance: u16,
+    /// fraction of the sprite pixels left visible by the sprites drawn over it and by the image borders
+    pub visibility: f32,
+}
+
+impl Label {
+    /// the label in the (x, y, width, height) crop, in pixels, of an image of the given (width, height).
+    /// The box is clipped to the crop, returns None if the crop holds less than `min_coverage` of it
+    pub fn crop(&self, image_size: (f32, f32), crop: (f32, f32, f32, f32), min_coverage: f32) -> Option<Label> {
+        let (image_width, image_height) = image_size;
+        let (crop_x, crop_y, crop_width, crop_height) = crop;
+
+        let bounds = &self.bounds;
+        let x_min = (bounds.x_center - bounds.width / 2.0) * image_width;
+        let y_min = (bounds.y_center - bounds.height / 2.0) * image_height;
+        let x_max = (bounds.x_center + bounds.width / 2.0) * image_width;
+        let y_max = (bounds.y_center + bounds.height / 2.0) * image_height;
+
+        let clipped_x_min = x_min.max(crop_x);
+        let clipped_y_min = y_min.max(crop_y);
+        let clipped_x_max = x_max.min(crop_x + crop_width);
+        let clipped_y_max = y_max.min(crop_y + crop_height);
+
+        if clipped_x_min >= clipped_x_max || clipped_y_min >= clipped_y_max {
+            return None;
+        }
+
+        let area = (x_max - x_min) * (y_max - y_min);
+        let clipped_area = (clipped_x_max - clipped_x_min) * (clipped_y_max - clipped_y_min);
+        if clipped_area < area * min_coverage {
+            return None;
+        }
+
+        Some(Label {
+            class: self.class,
+            bounds: Bounds {
+                x_center: ((clipped_x_min + clipped_x_max) / 2.0 - crop_x) / crop_width,
+                y_center: ((clipped_y_min + clipped_y_max) / 2.0 - crop_y) / crop_height,
+                width: (clipped_x_max - clipped_x_min) / crop_width,
+                height: (clipped_y_max - clipped_y_min) / crop_height,
+            },
+            instance: self.instance,
+            visibility: self.visibility,
+        })
+    }
 }
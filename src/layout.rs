@@ -0,0 +1,201 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::troop::InvalidTroopLevel;
+use crate::village::{Component, Village, VillageOperationError};
+
+/// On-disk representation of a village, one entry per placed component.
+#[derive(Serialize, Deserialize)]
+pub struct VillageLayout {
+    pub components: Vec<PlacedComponent>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlacedComponent {
+    pub cell: Cell,
+    pub component: Component,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum LayoutFormat {
+    Json,
+    Ron,
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// with the position of the error in the file, 0:0 when it comes from the serialization
+    Ron(ron::error::SpannedError),
+    UnknownFormat(String),
+    Village(VillageOperationError),
+    Troop(InvalidTroopLevel),
+}
+
+pub type LayoutResult<R> = Result<R, LayoutError>;
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::Io(err) => write!(f, "{err}"),
+            LayoutError::Json(err) => write!(f, "invalid json: {err}"),
+            LayoutError::Ron(err) if err.position.line == 0 => write!(f, "invalid ron: {}", err.code),
+            LayoutError::Ron(err) => write!(
+                f,
+                "invalid ron at line {}, column {}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            LayoutError::UnknownFormat(path) => write!(f, "{path} is neither a .json nor a .ron file"),
+            LayoutError::Village(err) => write!(f, "invalid village: {err}"),
+            LayoutError::Troop(err) => write!(f, "invalid attack plan: {err}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for LayoutError {
+    fn from(value: std::io::Error) -> Self {
+        LayoutError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for LayoutError {
+    fn from(value: serde_json::Error) -> Self {
+        LayoutError::Json(value)
+    }
+}
+
+impl From<ron::Error> for LayoutError {
+    fn from(value: ron::Error) -> Self {
+        LayoutError::Ron(ron::error::SpannedError {
+            code: value,
+            position: ron::error::Position { line: 0, col: 0 },
+        })
+    }
+}
+
+impl From<ron::error::SpannedError> for LayoutError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        LayoutError::Ron(value)
+    }
+}
+
+impl From<VillageOperationError> for LayoutError {
+    fn from(value: VillageOperationError) -> Self {
+        LayoutError::Village(value)
+    }
+}
+
+impl From<InvalidTroopLevel> for LayoutError {
+    fn from(value: InvalidTroopLevel) -> Self {
+        LayoutError::Troop(value)
+    }
+}
+
+impl LayoutFormat {
+    /// guess the format from the file extension (`.json` or `.ron`)
+    pub fn from_path(path: &Path) -> LayoutResult<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(LayoutFormat::Json),
+            Some("ron") => Ok(LayoutFormat::Ron),
+            _ => Err(LayoutError::UnknownFormat(path.display().to_string())),
+        }
+    }
+}
+
+/// deserialize any layout-like file (village layouts, attack plans...) from a json or ron file
+pub fn read_file<T: for<'de> Deserialize<'de>>(path: impl AsRef<Path>) -> LayoutResult<T> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)?;
+
+    match LayoutFormat::from_path(path)? {
+        LayoutFormat::Json => Ok(serde_json::from_str(&content)?),
+        LayoutFormat::Ron => Ok(ron::from_str(&content)?),
+    }
+}
+
+/// serialize any layout-like value to a json or ron file
+pub fn write_file<T: Serialize>(path: impl AsRef<Path>, value: &T) -> LayoutResult<()> {
+    let path = path.as_ref();
+
+    let content = match LayoutFormat::from_path(path)? {
+        LayoutFormat::Json => serde_json::to_string_pretty(value)?,
+        LayoutFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?,
+    };
+
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+impl Village {
+    pub fn from_layout(layout: VillageLayout) -> LayoutResult<Village> {
+        let mut village = Village::default();
+
+        for PlacedComponent { cell, component } in layout.components {
+            village.add_component(cell, component)?;
+        }
+
+        Ok(village)
+    }
+
+    pub fn to_layout(&self) -> VillageLayout {
+        VillageLayout {
+            components: self
+                .iter_components()
+                .map(|(_, cell, component)| PlacedComponent {
+                    cell,
+                    component: component.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> LayoutResult<Village> {
+        Village::from_layout(read_file(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> LayoutResult<()> {
+        write_file(path, &self.to_layout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::village::Village;
+
+    fn assert_round_trip(extension: &str) {
+        let village = Village::load("layouts/example_village.ron").unwrap();
+
+        let path = std::env::temp_dir().join(format!("coc-layout-round-trip-{}.{extension}", std::process::id()));
+        village.save(&path).unwrap();
+        let loaded = Village::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let components = |village: &Village| serde_json::to_value(village.to_layout()).unwrap();
+        assert_eq!(components(&loaded.unwrap()), components(&village));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        assert_round_trip("json");
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        assert_round_trip("ron");
+    }
+
+    #[test]
+    fn ron_errors_tell_where_they_are() {
+        let path = std::env::temp_dir().join(format!("coc-layout-invalid-{}.ron", std::process::id()));
+        std::fs::write(&path, "(\n    components: [\n        (cell: (x: 1, y: 2),,\n    ],\n)\n").unwrap();
+        let loaded = Village::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let message = loaded.err().unwrap().to_string();
+        assert!(message.starts_with("invalid ron at line 3, column "), "{message}");
+    }
+}
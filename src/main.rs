@@ -1,498 +1,202 @@
-use std::env::current_dir;
-use std::fs::OpenOptions;
-use std::future::Future;
-use std::io::Write;
+use std::path::Path;
 
-use image::Rgba;
-use lazy_static::lazy_static;
-use rand::Rng;
-use tokio::task::JoinSet;
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
 
-use crate::assets_render::Asset;
 use crate::attack_simulation::AttackPlan;
-use crate::buidling::{
-    ArcherDefenceState, Building, BuildingCharacteristics, BuildingType, MissileDefenceState,
-};
 use crate::cell::Cell;
-use crate::label::Bounds;
-use crate::position::Pos;
-use crate::render::{BUILDINGS_ASSETS_FILENAMES, Image, render, render_logs, RenderedScenery};
-use crate::scenery::Scenery;
-use crate::troop::{Troop, TroopType};
-use crate::village::{Component, ComponentType, Village, VillageOperationError, VillageOperationResult};
-use crate::wall::Wall;
+use crate::cli::{Cli, Command};
+use crate::render::{render, render_logs, RenderOptions};
+use crate::village::{Village, VillageOperationError};
 
 mod assets_render;
 mod attack_simulation;
 mod buidling;
 mod cell;
+mod class_registry;
+mod cli;
+mod dataset;
+mod generator;
 mod label;
+mod layout;
 mod pathfinding;
 mod position;
 mod render;
 mod scenery;
+mod seed;
 mod troop;
+mod validation;
 mod village;
 mod wall;
 
-const IMAGE_COUNT: usize = 20000;
+fn main() {
+    let cli = Cli::parse();
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 20)]
-async fn main() {
-    // village_generation().await;
-    // assets_mess_generation().await;
-    village_attack_simulation().await;
-}
-
-async fn village_attack_simulation() {
-    let village = create_village().unwrap();
-
-    let attack_plan = AttackPlan {
-        initial_placements: vec![
-            Troop {
-                tpe: TroopType::Barbarian,
-                pos: Pos::new(21.0, 21.0),
-            },
-            Troop {
-                tpe: TroopType::Barbarian,
-                pos: Pos::new(0.0, 21.0),
-            },
-            Troop {
-                tpe: TroopType::Giant,
-                pos: Pos::new(0.0, 0.0),
-            },
-            Troop {
-                tpe: TroopType::Giant,
-                pos: Pos::new(44.0, 21.0),
-            },
-            Troop {
-                tpe: TroopType::Barbarian,
-                pos: Pos::new(44.0, 44.0),
-            },
-        ],
-    };
-
-    // let simulation_result =
-    //     attack_simulation::simulate_attack(63, &village, &attack_plan);
-
-    let mut render_result = render(&village).unwrap();
-
-    // render_result.image = render_logs(
-    //     render_result.image,
-    //     village.scenery(),
-    //     simulation_result.evolution_logs,
-    // )
-    //     .unwrap();
-
-    render_result
-        .image
-        .save("out/simulations/simulation.png")
-        .unwrap();
-}
-
-lazy_static! {
-    static ref ASSETS: Vec<Asset> = {
-        let assets_folders = ["assets/sprites/buildings/"];
-
-        let mut assets = Vec::new();
-
-        print!("{}", current_dir().unwrap().display());
-
-        for folder in assets_folders {
-            for file in std::fs::read_dir(folder).unwrap() {
-                let file = file.unwrap();
-
-                let path = format!("{}", file.path().display());
-                assets.push(Asset {
-                    path,
-                    class: assets.len(),
-                });
-            }
+    match cli.command {
+        Command::GenerateVillages(args) => {
+            runtime(args.workers).block_on(dataset::village_generation(&args))
         }
-        assets
-    };
-}
-
-async fn assets_mess_generation() {
-    let assets_folders = ["assets/sprites/buildings"];
-
-    let mut assets = Vec::new();
-
-    for folder in assets_folders {
-        for file in std::fs::read_dir(folder).unwrap() {
-            let path = format!("{}", file.unwrap().path().display());
-            assets.push(Asset {
-                path,
-                class: assets.len(),
-            });
+        Command::GenerateAssetMess(args) => {
+            runtime(args.workers).block_on(dataset::assets_mess_generation(&args))
         }
+        Command::GenerateLayout {
+            town_hall_level,
+            seed,
+            output,
+        } => generate_layout(town_hall_level, seed, &output),
+        Command::Edit {
+            layout,
+            remove,
+            moves,
+            levels,
+            output,
+        } => edit_layout(&layout, &remove, &moves, &levels, output.as_deref().unwrap_or(&layout)),
+        Command::Render { layout, output } => render_layout(&layout, &output),
+        Command::Simulate {
+            layout,
+            plan,
+            duration,
+            seed,
+            output,
+        } => village_attack_simulation(&layout, &plan, duration, seed, &output),
     }
-
-    generate_dataset(
-        ASSETS.len(),
-        ASSETS.iter().map(|a| &a.path),
-        generate_asset_mess_image,
-    )
-        .await;
 }
 
-async fn generate_asset_mess_image(image_dir: &str, labels_dir: &str, id: usize) {
-    const ASSETS_PER_IMAGE: u16 = 75;
-
-    let mut used_assets = Vec::new();
-
-    for _ in 0..ASSETS_PER_IMAGE {
-        let mut rng = rand::thread_rng();
+fn runtime(workers: usize) -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()
+        .expect("could not start the runtime")
+}
 
-        used_assets.push(&ASSETS[rng.gen_range(0..ASSETS.len())])
+fn generate_layout(town_hall_level: u8, seed: u64, output: &Path) {
+    let mut rng = seed::entry_rng(seed, "layout", 0);
+    let village = generator::generate_village(&mut rng, town_hall_level).unwrap_or_else(|err| {
+        Cli::command()
+            .error(ErrorKind::ValueValidation, format!("could not generate the village: {err}"))
+            .exit()
+    });
+
+    create_parent_dir(output);
+    if let Err(err) = village.save(output) {
+        Cli::command()
+            .error(ErrorKind::Io, format!("could not save {}: {err}", output.display()))
+            .exit()
     }
-
-    let grid = image::open("assets/grid.png").unwrap();
-    let result = assets_render::render(grid.to_rgba8(), &used_assets).unwrap();
-
-    generate_dataset_entries(result, image_dir, labels_dir, id).await;
 }
 
-async fn village_generation() {
-    generate_dataset(
-        BUILDINGS_ASSETS_FILENAMES.len(),
-        BUILDINGS_ASSETS_FILENAMES.iter(),
-        generate_village_image,
-    )
-        .await;
+/// loads the layout file, exits with an error if it cannot be read
+fn load_village(layout: &Path) -> Village {
+    Village::load(layout).unwrap_or_else(|err| {
+        Cli::command()
+            .error(ErrorKind::Io, format!("could not load {}: {err}", layout.display()))
+            .exit()
+    })
 }
 
-async fn generate_dataset<'a, F>(
-    asset_count: usize,
-    assets: impl Iterator<Item=&String>,
-    generate_entries: impl Fn(&'a str, &'a str, usize) -> F + 'a,
-) where
-    F: Future<Output=()>,
-    F: Send + 'static,
-{
-    std::fs::create_dir_all("out/images/train").unwrap();
-    std::fs::create_dir_all("out/images/val").unwrap();
-    std::fs::create_dir_all("out/images/test").unwrap();
-    std::fs::create_dir_all("out/labels/train").unwrap();
-    std::fs::create_dir_all("out/labels/val").unwrap();
-    std::fs::create_dir_all("out/labels/test").unwrap();
+fn edit_layout(layout: &Path, remove: &[Cell], moves: &[(Cell, Cell)], levels: &[(Cell, u8)], output: &Path) {
+    let mut village = load_village(layout);
 
-    let mut set = JoinSet::new();
-
-    for x in 0..(IMAGE_COUNT as f32 * 0.8f32) as usize {
-        set.spawn(generate_entries("out/images/train", "out/labels/train", x));
+    // the components are all found before the first edit, an edit does not change the ids
+    let component_at = |cell: Cell| match village.get_component_at(cell) {
+        Some((id, _)) => id,
+        None => Cli::command()
+            .error(ErrorKind::ValueValidation, format!("no component covers the cell {},{}", cell.x, cell.y))
+            .exit(),
+    };
+    let remove = remove.iter().map(|cell| component_at(*cell)).collect::<Vec<_>>();
+    let moves = moves.iter().map(|(cell, to)| (component_at(*cell), *to)).collect::<Vec<_>>();
+    let levels = levels.iter().map(|(cell, level)| (component_at(*cell), *level)).collect::<Vec<_>>();
+
+    let result = remove
+        .into_iter()
+        .try_for_each(|id| village.remove_component(id).map(|_| ()))
+        .and_then(|_| moves.into_iter().try_for_each(|(id, to)| village.move_component(id, to)))
+        .and_then(|_| {
+            levels.into_iter().try_for_each(|(id, level)| {
+                let component = village.get_component(id).ok_or(VillageOperationError::ComponentNotFound(id))?;
+                village.replace_component(id, component.with_level(level)).map(|_| ())
+            })
+        });
+
+    if let Err(err) = result {
+        Cli::command()
+            .error(ErrorKind::ValueValidation, format!("could not edit the village: {err}"))
+            .exit()
     }
 
-    for x in 0..(IMAGE_COUNT as f32 * 0.2f32) as usize {
-        set.spawn(generate_entries("out/images/val", "out/labels/val", x));
+    create_parent_dir(output);
+    if let Err(err) = village.save(output) {
+        Cli::command()
+            .error(ErrorKind::Io, format!("could not save {}: {err}", output.display()))
+            .exit()
     }
+}
 
-    for x in 0..(IMAGE_COUNT as f32 * 0.2f32) as usize {
-        set.spawn(generate_entries("out/images/test", "out/labels/test", x));
-    }
+fn render_layout(layout: &Path, output: &Path) {
+    let village = load_village(layout);
 
-    let mut progress = 0;
-    let total = set.len();
+    let render_result = render(&village, RenderOptions::default()).unwrap();
 
-    while let Some(res) = set.join_next().await {
-        res.unwrap();
+    create_parent_dir(output);
+    render_result.image.save(output).unwrap();
+}
 
-        print!("progress : {progress}/{total}\r");
-        progress += 1;
+fn village_attack_simulation(layout: &Path, plan: &Path, duration: u32, seed: u64, output: &Path) {
+    let village = load_village(layout);
+    let attack_plan = AttackPlan::load(plan).unwrap_or_else(|err| {
+        Cli::command()
+            .error(ErrorKind::Io, format!("could not load {}: {err}", plan.display()))
+            .exit()
+    });
+
+    let simulation_result = attack_simulation::simulate_attack(duration, &village, &attack_plan);
+
+    for (troop, outcome) in simulation_result.troops.iter().zip(&simulation_result.troops_outcomes) {
+        let fate = match outcome.death_time {
+            Some(time) => format!("died after {time:.1}s"),
+            None => format!("survived with {:.0} life points", outcome.life_points),
+        };
+        println!(
+            "{:?} level {}: {fate}, dealt {:.0} damages",
+            troop.tpe, troop.level, outcome.damage_dealt
+        );
     }
-
-    println!("Generating YOLO dataset configuration");
-    let mut dataset_file = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open("out/data.yaml")
-        .expect("cannot open file");
-
-    writeln!(
-        dataset_file,
-        "train: /home/maxime/Projects/coc-base-generator/out/images/train"
-    )
-        .unwrap();
-    writeln!(
-        dataset_file,
-        "val: /home/maxime/Projects/coc-base-generator/out/images/val"
-    )
-        .unwrap();
-    writeln!(dataset_file, "nc: {}", asset_count).unwrap();
-    write!(dataset_file, "classes: [").unwrap();
-
-    if let Some((first, other)) = assets.collect::<Vec<_>>().split_first() {
-        write!(dataset_file, "{first}").unwrap();
-        for it in other {
-            write!(dataset_file, ", {it}").unwrap();
+    println!(
+        "{:.0}% destruction, {} stars",
+        simulation_result.destruction, simulation_result.stars
+    );
+
+    // the destruction only changes when a building is destroyed
+    let mut last_destruction = 0.0;
+    for (time, destruction) in &simulation_result.destruction_over_time {
+        if *destruction != last_destruction {
+            println!("{time:>6.1}s: {destruction:.0}%");
+            last_destruction = *destruction;
         }
     }
-    writeln!(dataset_file, "]").unwrap();
-}
-
-async fn generate_village_image(image_dir: &str, labels_dir: &str, id: usize) {
-    // let village = generate_village(&scenery).unwrap();
-    let village = create_village().unwrap();
-    //
-    // let village = Village::default();
-
-    let result = render(&village).unwrap();
 
-    generate_dataset_entries(result, image_dir, labels_dir, id).await;
-}
-
-async fn generate_dataset_entries(
-    mut result: RenderedScenery,
-    image_dir: &str,
-    labels_dir: &str,
-    id: usize,
-) {
-    result
-        .image
-        .save(format!("{image_dir}/village_{id}.png"))
-        .unwrap();
-
-    result.image = add_random_dots(result.image, 450, 2, 5);
-    result
-        .image
-        .save(format!("{image_dir}/village_{id}_with_dots.png"))
-        .unwrap();
-
-    let mut labels_file = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(format!("{labels_dir}/village_{id}.txt"))
-        .expect("cannot open file");
-
-    for label in result.labels {
-        let Bounds {
-            x_center,
-            y_center,
-            width,
-            height,
-        } = label.bounds;
-        writeln!(
-            labels_file,
-            "{} {x_center} {y_center} {width} {height}",
-            label.class
-        )
-            .unwrap();
-    }
+    let options = RenderOptions {
+        show_life_points: true,
+        ..RenderOptions::default()
+    };
+    let mut render_result = render(&simulation_result.village, options).unwrap();
 
-    std::fs::copy(
-        format!("{labels_dir}/village_{id}.txt"),
-        format!("{labels_dir}/village_{id}_with_dots.txt"),
+    render_result.image = render_logs(
+        render_result.image,
+        village.scenery(),
+        simulation_result.evolution_logs,
+        &mut seed::entry_rng(seed, "simulation", 0),
     )
         .unwrap();
-}
-
-fn add_random_dots(mut image: Image, dots_count: u16, min_size: u32, max_size: u32) -> Image {
-    let mut rng = rand::thread_rng();
-    for _ in 0..dots_count {
-        let radius = rng.gen_range(min_size..=max_size);
-        let x = rng.gen_range(0..image.width() - radius) as i32;
-        let y = rng.gen_range(0..image.height() - radius) as i32;
 
-        image = imageproc::drawing::draw_filled_circle(
-            &image,
-            (x, y),
-            radius as i32,
-            Rgba([255, 255, 255, 255]),
-        )
-    }
-
-    image
+    create_parent_dir(output);
+    render_result.image.save(output).unwrap();
 }
 
-fn generate_village(scenery: &Scenery) -> VillageOperationResult<Village> {
-    let mut rng = rand::thread_rng();
-
-    let mut village = Village::default();
-
-    for x in 0..scenery.params().plate_width_cells as i16 {
-        for y in 0..scenery.params().plate_height_cells as i16 {
-            let building_type: BuildingType = rng.gen();
-            let level = rng.gen_range(building_type.level_range());
-            let cell = Cell::new(x, y);
-
-            let building = Building {
-                building_type,
-                level,
-                characteristics: BuildingCharacteristics::Passive,
-            };
-
-            match village.add_component(cell, Component {
-                kind: ComponentType::Building(building),
-                life_points: None,
-            }) {
-                Ok(_) => {}
-                Err(VillageOperationError::ComponentCollides) => {} //do not panic if the building collides with another one
-            };
-        }
+fn create_parent_dir(path: &Path) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
     }
-
-    Ok(village)
-}
-
-fn create_village() -> VillageOperationResult<Village> {
-    let mut village = Village::default();
-
-    village.add_component(Cell::new(10, 10), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(0,0), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(1,0), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(2,0), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(3,0), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(4,0), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(4,1), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(4,2), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(4,3), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(11, 10), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(11, 11), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(12, 10), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(10, 12), Component {
-        kind: ComponentType::Wall(Wall {
-            level: 10
-        }),
-        life_points: Some(20.0)
-    })?;
-
-    village.add_component(Cell::new(20, 15), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::ArmyCamp,
-            level: 3,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(400f32),
-    })?;
-
-    village.add_component(Cell::new(3, 6), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::Mortar(MissileDefenceState::Regular),
-            level: 13,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(250f32),
-    })?;
-
-
-    village.add_component(Cell::new(0, 1), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::BuilderHut,
-            level: 1,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(360f32),
-    })?;
-
-    village.add_component(Cell::new(8, 15), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::ArcherTower(ArcherDefenceState::Regular),
-            level: 12,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(1000f32),
-    })?;
-
-    village.add_component(Cell::new(7, 12), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::HiddenTesla,
-            level: 9,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(780f32),
-    })?;
-
-    village.add_component(Cell::new(3, 3), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::HiddenTesla,
-            level: 9,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(47f32),
-    })?;
-
-    village.add_component(Cell::new(20, 35), Component {
-        kind: ComponentType::Building(Building {
-            building_type: BuildingType::TownHall,
-            level: 11,
-            characteristics: BuildingCharacteristics::Passive,
-        }),
-        life_points: Some(186f32),
-    })?;
-
-    Ok(village)
 }
@@ -2,11 +2,12 @@ use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashSet};
 use std::collections::HashMap;
 
+use crate::buidling::BuildingCategory;
 use crate::cell::Cell;
 use crate::position::Pos;
 use crate::scenery::Scenery;
-use crate::troop::Troop;
-use crate::village::{Component, ComponentId, is_defensive_building, Village};
+use crate::troop::{FavouriteTarget, Troop};
+use crate::village::{Component, ComponentId, ComponentType, is_defensive_building, Village};
 
 #[derive(Debug, PartialEq, Eq)]
 struct Node {
@@ -68,13 +69,18 @@ fn reconstruct_path(came_from: &HashMap<Pos, Pos>, current: Pos) -> Vec<Pos> {
     path
 }
 
-fn pathfind(start: Pos, goal: Pos, village: &Village, scenery: &Scenery) -> Vec<Pos> {
+/// shortest path from start to goal, through the walls if `through_walls` is set.
+/// Returns an empty path if the goal cannot be reached
+fn pathfind(start: Pos, goal: Pos, village: &Village, scenery: &Scenery, through_walls: bool) -> Vec<Pos> {
     let width = scenery.params().plate_width_cells as i16;
     let height = scenery.params().plate_height_cells as i16;
 
+    // troops can stop between two cells, the search starts from the closest one
+    let search_start = Pos::new(start.x.round(), start.y.round());
+
     let mut open_set = BinaryHeap::new();
     open_set.push(Node {
-        position: start,
+        position: search_start,
         cost: 0,
     });
 
@@ -82,21 +88,25 @@ fn pathfind(start: Pos, goal: Pos, village: &Village, scenery: &Scenery) -> Vec<
     let mut g_score: HashMap<Pos, i32> = HashMap::new();
     let mut closed_set: HashSet<Pos> = HashSet::new();
 
-    g_score.insert(start, 0);
+    g_score.insert(search_start, 0);
 
     while let Some(Node {
         position: current, ..
     }) = open_set.pop()
     {
         if current == goal {
-            return reconstruct_path(&came_from, current);
+            let mut path = reconstruct_path(&came_from, current);
+            if search_start != start {
+                path.insert(0, start);
+            }
+            return path;
         }
 
         closed_set.insert(current);
 
         for neighbor in neighbors(current, width, height) {
             if goal != neighbor
-                && (closed_set.contains(&neighbor) || village.is_cell_blocked(neighbor.to_cell()))
+                && (closed_set.contains(&neighbor) || is_cell_blocked(village, neighbor.to_cell(), through_walls))
             {
                 continue;
             }
@@ -116,27 +126,87 @@ fn pathfind(start: Pos, goal: Pos, village: &Village, scenery: &Scenery) -> Vec<
     vec![]
 }
 
-fn list_cells_of_component(cell: Cell, comp: &Component) -> Vec<Cell> {
-    let building_diameter = comp.get_plot_size().cell_diameter();
+fn is_cell_blocked(village: &Village, cell: Cell, through_walls: bool) -> bool {
+    if through_walls && live_wall_at(village, cell).is_some() {
+        return false;
+    }
+    village.is_cell_blocked(cell)
+}
 
-    let mut cells = Vec::new();
+/// the wall at the cell if troops can break it, walls without life points cannot be damaged
+fn live_wall_at(village: &Village, cell: Cell) -> Option<ComponentId> {
+    village
+        .get_component_at(cell)
+        .filter(|(_, comp)| {
+            matches!(comp.kind, ComponentType::Wall(_)) && comp.life_points.is_some_and(|lp| lp > 0.0)
+        })
+        .map(|(id, _)| id)
+}
 
-    for x in 0..building_diameter {
-        for y in 0..building_diameter {
-            cells.push(Cell {
-                x: cell.x + x as i16,
-                y: cell.y + y as i16,
-            });
-        }
+/// path to the target, or to the first wall on the way if the walls close the target in.
+/// None if the target cannot be reached, even by breaking walls
+fn route_to(troop: &Troop, goal: Pos, target: ComponentId, village: &Village, scenery: &Scenery) -> Option<(Vec<Pos>, ComponentId)> {
+    let path = pathfind(troop.pos, goal, village, scenery, false);
+    if !path.is_empty() {
+        return Some((path, target));
+    }
+
+    // the troop breaks the first wall of the shortest way, from the cell before it
+    let path = pathfind(troop.pos, goal, village, scenery, true);
+    let first_wall = path
+        .iter()
+        .enumerate()
+        .find_map(|(idx, pos)| live_wall_at(village, pos.to_cell()).map(|wall| (idx, wall)));
+
+    match first_wall {
+        Some((idx, wall)) => Some((path[..idx.max(1)].to_vec(), wall)),
+        None if path.is_empty() => None,
+        None => Some((path, target)),
     }
+}
 
-    cells
+fn is_target(favourite: FavouriteTarget, comp: &Component) -> bool {
+    let ComponentType::Building(building) = &comp.kind else {
+        return favourite == FavouriteTarget::Walls;
+    };
+
+    // traps are hidden, troops never go for them
+    if building.building_type.is_trap() {
+        return false;
+    }
+
+    match favourite {
+        FavouriteTarget::Defenses => is_defensive_building(comp),
+        FavouriteTarget::Resources => building.building_type.category() == BuildingCategory::Resource,
+        FavouriteTarget::Walls => false,
+        FavouriteTarget::Any => true,
+    }
 }
 
+/// route to the closest favourite target of the troop, or to the closest building when none is left
 pub fn find_route_to_next_building(
     troop: &Troop,
     village: &Village,
     scenery: &Scenery,
+) -> Option<(Vec<Pos>, ComponentId)> {
+    let mut favourite = troop.tpe.favourite_target();
+    if favourite == FavouriteTarget::Defenses && village.state().remaining_defenses == 0 {
+        favourite = FavouriteTarget::Any;
+    }
+
+    find_route_to_closest_target(troop, favourite, village, scenery).or_else(|| {
+        if favourite == FavouriteTarget::Any {
+            return None;
+        }
+        find_route_to_closest_target(troop, FavouriteTarget::Any, village, scenery)
+    })
+}
+
+fn find_route_to_closest_target(
+    troop: &Troop,
+    favourite: FavouriteTarget,
+    village: &Village,
+    scenery: &Scenery,
 ) -> Option<(Vec<Pos>, ComponentId)> {
     #[derive(Copy, Clone)]
     struct ClosestBuildingCellInfo {
@@ -145,36 +215,39 @@ pub fn find_route_to_next_building(
         distance: f32,
     }
 
-    let mut closest_building: Option<ClosestBuildingCellInfo> = None;
+    let mut targets: Vec<ClosestBuildingCellInfo> = Vec::new();
 
-    // find the closest building cell
+    // find the closest cell of each target
     for (comp_id, cell, comp) in village.iter_components() {
-        if comp.life_points.is_some_and(|lp| lp == 0f32) {
-            continue; //ignore dead buildings
+        if !comp.life_points.is_some_and(|lp| lp > 0f32) {
+            continue; //ignore dead buildings, and the ones that cannot be damaged
         }
-        
-        if village.state().remaining_defenses > 0 && is_defensive_building(comp) {
-            //if there is no defense left, then target other buildings
-            continue
+
+        if !is_target(favourite, comp) {
+            continue;
         }
 
-        for building_cell in list_cells_of_component(cell, comp) {
+        for building_cell in comp.footprint(cell) {
             let distance = building_cell.to_pos().distance(troop.pos);
-            if closest_building.is_none() || closest_building.is_some_and(|b| distance < b.distance)
-            {
-                closest_building = Some(ClosestBuildingCellInfo {
-                    distance,
-                    id: comp_id,
-                    pos: building_cell.to_pos(),
-                });
+            let closest_cell = ClosestBuildingCellInfo {
+                distance,
+                id: comp_id,
+                pos: building_cell.to_pos(),
+            };
+            match targets.last_mut() {
+                Some(target) if target.id == comp_id => {
+                    if distance < target.distance {
+                        *target = closest_cell;
+                    }
+                }
+                _ => targets.push(closest_cell),
             }
         }
     }
 
-    closest_building.map(|closest_building| {
-        (
-            pathfind(troop.pos, closest_building.pos, village, scenery),
-            closest_building.id,
-        )
-    })
+    // the closest target can be out of reach, the troop goes for the next closest one then
+    targets.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    targets
+        .into_iter()
+        .find_map(|target| route_to(troop, target.pos, target.id, village, scenery))
 }
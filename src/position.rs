@@ -1,8 +1,9 @@
 use crate::cell::Cell;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Pos {
     pub x: f32,
     pub y: f32,
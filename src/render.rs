@@ -2,51 +2,47 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 
 use image::{GenericImage, GenericImageView, Pixel, Rgb, Rgba};
-use image::imageops::FilterType;
 use imageproc::drawing;
 use imageproc::point::Point;
-use lazy_static::lazy_static;
 use rand::Rng;
 
 use crate::attack_simulation::{EvolutionLog, TroopId};
-use crate::buidling::BUILDING_ASSETS_FOLDER;
 use crate::cell::Cell;
-use crate::label::Label;
+use crate::label::LabelOptions;
+use crate::label::{Label, WallLabels};
 use crate::position::Pos;
 use crate::render::building::{draw_plot, render_building};
-use crate::render::wall::render_wall;
+use crate::render::instance::{BACKGROUND_INSTANCE, compute_visibility, InstanceMap};
+use crate::render::wall::{render_wall, wall_segments};
 use crate::scenery::Scenery;
 use crate::village::{Component, ComponentType, Village};
 
 mod building;
+pub mod cache;
+pub mod instance;
 mod wall;
 
 pub type Image = imageproc::definitions::Image<Rgba<u8>>;
 
+/// How a village is rendered
+#[derive(Copy, Clone, Default)]
+pub struct RenderOptions {
+    pub labels: LabelOptions,
+    /// draws the life points of the components over them. They are not labeled, so dataset images must not show them
+    pub show_life_points: bool,
+}
+
 pub struct RenderedScenery {
     pub image: Image,
     pub labels: Vec<Label>,
-}
-
-lazy_static! {
-    pub static ref BUILDINGS_ASSETS_FILENAMES: Vec<String> =
-        std::fs::read_dir(BUILDING_ASSETS_FOLDER)
-            .unwrap()
-            .map(|file| file
-                .unwrap()
-                .path()
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string())
-            .collect();
+    pub instances: InstanceMap,
 }
 
 pub fn render_logs(
     mut scenery_image: Image,
     scenery: &Scenery,
     logs: Vec<EvolutionLog>,
+    rng: &mut impl Rng,
 ) -> Result<Image, String> {
     let mut troops_colors: HashMap<TroopId, Rgba<u8>> = HashMap::new();
 
@@ -54,7 +50,7 @@ pub fn render_logs(
         for (troop, path) in log.troops_paths {
             let color = *troops_colors
                 .entry(troop)
-                .or_insert_with(|| rand_color().to_rgba());
+                .or_insert_with(|| rand_color(rng).to_rgba());
 
             if let Some((first, others)) = path.split_first() {
                 let (mut last_pixel_x, mut last_pixel_y) =
@@ -80,14 +76,12 @@ pub fn render_logs(
     Ok(scenery_image)
 }
 
-fn rand_color() -> Rgb<u8> {
-    let mut rng = rand::thread_rng();
+fn rand_color(rng: &mut impl Rng) -> Rgb<u8> {
     Rgb([rng.gen(), rng.gen(), rng.gen()])
 }
 
-pub fn render(village: &Village) -> Result<RenderedScenery, String> {
-    let background_image = image::open("assets/scenery.png").unwrap();
-    let mut buffer = background_image.into_rgba8();
+pub fn render(village: &Village, options: RenderOptions) -> Result<RenderedScenery, String> {
+    let mut buffer = cache::image(&village.scenery().params().image_path).as_ref().clone();
 
     // buffer = draw_debug_grid(&buffer, scenery);
 
@@ -130,45 +124,118 @@ pub fn render(village: &Village) -> Result<RenderedScenery, String> {
         }
     }
 
-    let mut labels = Vec::new();
+    // plots are part of the ground, they are not in the instance map
+    let mut instances = InstanceMap::new(buffer.width(), buffer.height());
+    let mut labels: Vec<Label> = Vec::new();
+    let mut sprite_pixels = Vec::new();
+
+    let wall_segments = match options.labels.walls {
+        WallLabels::Segments => wall_segments(village),
+        _ => HashMap::new(),
+    };
+    // index of the label of each wall segment
+    let mut segment_labels: HashMap<usize, usize> = HashMap::new();
 
     for (_, cell, component) in components {
-        if let Some(label) = render_component(&mut buffer, village, cell, component) {
-            labels.push(label)
+        let segment = wall_segments.get(&cell).copied();
+        let segment_label = segment.and_then(|segment| segment_labels.get(&segment).copied());
+
+        let instance = match segment_label {
+            Some(label_idx) => labels[label_idx].instance,
+            None => labels.len() as u16 + 1,
+        };
+
+        let label = render_component(&mut buffer, &mut instances, village, cell, component, instance, options);
+        let Some((label, pixels)) = label else {
+            continue;
+        };
+
+        if let Some(label_idx) = segment_label {
+            labels[label_idx].bounds = labels[label_idx].bounds.union(&label.bounds);
+            sprite_pixels[label_idx] += pixels;
+            continue;
+        }
+
+        if let Some(segment) = segment {
+            segment_labels.insert(segment, labels.len());
         }
+        labels.push(label);
+        sprite_pixels.push(pixels);
     }
 
+    compute_visibility(&mut labels, &sprite_pixels, &instances);
+
     Ok(RenderedScenery {
         image: buffer,
         labels,
+        instances,
     })
 }
 
-fn render_component(buffer: &mut Image, village: &Village, cell: Cell, component: &Component) -> Option<Label> {
+/// renders a component, returns its label with the number of opaque pixels of its sprite if it is labeled
+fn render_component(
+    buffer: &mut Image,
+    instances: &mut InstanceMap,
+    village: &Village,
+    cell: Cell,
+    component: &Component,
+    instance: u16,
+    options: RenderOptions,
+) -> Option<(Label, u32)> {
+    let label_options = options.labels;
     match &component.kind {
         ComponentType::Building(building) => {
-            let bounds = render_building(buffer, village.scenery(), cell, component.life_points, building);
-            let class = BUILDINGS_ASSETS_FILENAMES
-                .iter()
-                .position(|filename| filename == &building.building_type.get_file_name(building.level))
-                .unwrap();
-            Some(Label { bounds, class })
+            let sprite_bounds = render_building(
+                buffer,
+                instances,
+                instance,
+                village.scenery(),
+                cell,
+                component.life_points.filter(|_| options.show_life_points),
+                building,
+            );
+            let class = label_options.taxonomy.class_id(&building.building_type, building.level);
+            // sprites fully out of the image have no tight bounds
+            let bounds = sprite_bounds.bounds(label_options.bounds)?;
+            let label = Label {
+                bounds,
+                class,
+                instance,
+                visibility: 1.0,
+            };
+            Some((label, sprite_bounds.opaque_pixels))
         }
         ComponentType::Wall(wall) => {
-            render_wall(buffer, village, cell, wall);
-            None
+            let class = match label_options.walls {
+                WallLabels::None => {
+                    // unlabeled walls still hide the buildings behind them
+                    render_wall(buffer, instances, BACKGROUND_INSTANCE, village, cell, wall);
+                    return None;
+                }
+                WallLabels::PerWall => {
+                    let connection = village.get_wall_connection_type(cell).expect("given cell does not hosts any wall");
+                    let taxonomy = label_options.taxonomy;
+                    taxonomy.named_class_id(&taxonomy.wall_class_name(wall.level, connection))
+                }
+                WallLabels::Segments => {
+                    let taxonomy = label_options.taxonomy;
+                    taxonomy.named_class_id(&taxonomy.wall_segment_class_name())
+                }
+            };
+
+            let sprite_bounds = render_wall(buffer, instances, instance, village, cell, wall);
+            let bounds = sprite_bounds.bounds(label_options.bounds)?;
+            let label = Label {
+                bounds,
+                class,
+                instance,
+                visibility: 1.0,
+            };
+            Some((label, sprite_bounds.opaque_pixels))
         }
     }
 }
 
-pub(self) fn resize_image_by_width(image: &Image, target_width: u32) -> Image {
-    let width = target_width;
-    let height = (image.height() as f32
-        * (target_width as f32 / image.width() as f32)) as u32;
-
-    image::imageops::resize(image, width, height, FilterType::Nearest)
-}
-
 fn draw_debug_grid(buffer: &Image, scenery: &Scenery) -> Image {
     let bottom_right_corner = scenery.params().bottom_right_corner;
     let bottom_left_corner = scenery.params().bottom_left_corner;
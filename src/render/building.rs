@@ -1,10 +1,9 @@
 use crate::buidling::{Building, PlotSize};
 use crate::cell::Cell;
-use crate::label::Bounds;
-use crate::render::{get_plate_pixel_position, Image, resize_image_by_width};
+use crate::label::{Bounds, SpriteBounds};
+use crate::render::{cache, get_plate_pixel_position, Image};
+use crate::render::instance::{InstanceMap, paint_sprite};
 use crate::scenery::Scenery;
-use ab_glyph::FontRef;
-use image::imageops::FilterType;
 use image::Rgba;
 use imageproc::drawing;
 
@@ -12,17 +11,15 @@ const BUILDING_ALIGNMENT_SHIFT_Y: i64 = 5;
 
 pub(super) fn render_building(
     scenery_image: &mut Image,
+    instances: &mut InstanceMap,
+    instance: u16,
     scenery: &Scenery,
     cell: Cell,
     life_points: Option<f32>,
     building: &Building,
-) -> Bounds {
+) -> SpriteBounds {
     let building_type = &building.building_type;
 
-    let building_image_path = building_type.get_file_path(building.level);
-    let building_image = image::open(building_image_path).unwrap();
-    let building_image = building_image.to_rgba8();
-
     let building_size = building_type.self_size();
 
     let building_size_width = scenery.cell_width() * building_size.cell_diameter() as f32;
@@ -40,7 +37,7 @@ pub(super) fn render_building(
 
     let target_width = (building_size_width * 2f32 - width_margin) as u32;
 
-    let building_image = resize_image_by_width(&building_image, target_width);
+    let building_image = cache::image_by_width(building_type.get_file_path(building.level), target_width);
 
     let (x, y) = get_plate_pixel_position(cell.to_pos(), scenery);
 
@@ -61,9 +58,16 @@ pub(super) fn render_building(
 
     image::imageops::overlay(
         scenery_image,
+        building_image.as_ref(),
+        translated_image_x,
+        translated_image_y,
+    );
+    let painted = paint_sprite(
+        instances,
         &building_image,
         translated_image_x,
         translated_image_y,
+        instance,
     );
     #[cfg(debug_assertions)]
     {
@@ -93,8 +97,6 @@ pub(super) fn render_building(
     let y_center_pixels = translated_image_y + building_image.height() as i64 / 2;
 
     if let Some(lp) = life_points {
-        let font = FontRef::try_from_slice(include_bytes!("../../assets/font.ttf")).unwrap();
-
         let (color, text) = if lp == 0.0 {
             (Rgba([255, 0, 0, 255]), "DEAD".to_string())
         } else {
@@ -107,16 +109,22 @@ pub(super) fn render_building(
             plot_center_x as i32,
             plot_center_y as i32,
             20.0,
-            &font,
+            cache::font(),
             &text,
         );
     }
 
-    Bounds {
-        x_center: x_center_pixels as f32 / scenery_image.width() as f32,
-        y_center: y_center_pixels as f32 / scenery_image.height() as f32,
-        height: building_image.height() as f32 / scenery_image.height() as f32,
-        width: building_image.width() as f32 / scenery_image.width() as f32,
+    SpriteBounds {
+        sprite: Bounds {
+            x_center: x_center_pixels as f32 / scenery_image.width() as f32,
+            y_center: y_center_pixels as f32 / scenery_image.height() as f32,
+            height: building_image.height() as f32 / scenery_image.height() as f32,
+            width: building_image.width() as f32 / scenery_image.width() as f32,
+        },
+        opaque: painted
+            .extent
+            .map(|extent| Bounds::from_extent(extent, scenery_image.width(), scenery_image.height())),
+        opaque_pixels: painted.opaque_pixels,
     }
 }
 
@@ -130,19 +138,11 @@ pub(super) fn draw_plot(
         return;
     };
 
-    let plot = image::open(plot_file).unwrap();
-    let plot = plot.as_rgba8().unwrap();
-
     let width_radius = (scenery.cell_width() * plot_size.cell_diameter() as f32) as u32;
     let height_radius = (scenery.cell_height() * plot_size.cell_diameter() as f32) as u32;
 
-    let resized_image = image::imageops::resize(
-        plot,
-        width_radius * 2,
-        height_radius * 2,
-        FilterType::Nearest,
-    );
+    let resized_image = cache::resized_image(plot_file, width_radius * 2, height_radius * 2);
     let (x, y) = get_plate_pixel_position(cell.to_pos(), scenery);
 
-    image::imageops::overlay(scenery_image, &resized_image, x, y - height_radius as i64)
+    image::imageops::overlay(scenery_image, resized_image.as_ref(), x, y - height_radius as i64)
 }
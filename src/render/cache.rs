@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use ab_glyph::FontRef;
+use image::imageops::FilterType;
+use lazy_static::lazy_static;
+
+use crate::render::Image;
+
+/// file of an image and its size, None for the size of the file
+type ImageKey = (PathBuf, Option<(u32, u32)>);
+
+lazy_static! {
+    static ref IMAGES: RwLock<HashMap<ImageKey, Arc<Image>>> = RwLock::new(HashMap::new());
+    static ref FONT: FontRef<'static> = FontRef::try_from_slice(include_bytes!("../../assets/font.ttf")).unwrap();
+}
+
+/// the font of the texts drawn on the renders
+pub fn font() -> &'static FontRef<'static> {
+    &FONT
+}
+
+/// the image of the file, read once and shared by every render
+pub fn image(path: impl AsRef<Path>) -> Arc<Image> {
+    cached(path.as_ref(), None, || image::open(path.as_ref()).unwrap().into_rgba8())
+}
+
+/// the image of the file resized to the given size, resized once per size
+pub fn resized_image(path: impl AsRef<Path>, width: u32, height: u32) -> Arc<Image> {
+    let path = path.as_ref();
+    cached(path, Some((width, height)), || {
+        image::imageops::resize(image(path).as_ref(), width, height, FilterType::Nearest)
+    })
+}
+
+/// the image of the file resized to the given width, keeping its aspect ratio
+pub fn image_by_width(path: impl AsRef<Path>, width: u32) -> Arc<Image> {
+    let path = path.as_ref();
+    let original = image(path);
+    let height = (original.height() as f32 * (width as f32 / original.width() as f32)) as u32;
+
+    resized_image(path, width, height)
+}
+
+fn cached(path: &Path, size: Option<(u32, u32)>, load: impl FnOnce() -> Image) -> Arc<Image> {
+    let key = (path.to_path_buf(), size);
+
+    if let Some(image) = IMAGES.read().unwrap().get(&key) {
+        return image.clone();
+    }
+
+    // loaded without the lock, two threads may load the same image but only one is kept
+    let image = Arc::new(load());
+    IMAGES.write().unwrap().entry(key).or_insert(image).clone()
+}
@@ -0,0 +1,147 @@
+use image::{ImageBuffer, Luma, Rgba, RgbaImage};
+use imageproc::contours::{BorderType, find_contours};
+use imageproc::geometry::{approximate_polygon_dp, contour_area};
+
+use crate::label::Label;
+
+/// Instance of every pixel of a rendered image, `0` is the background.
+/// Pixels of a sprite are overwritten by the sprites drawn after it, which makes the map account for occlusion
+pub type InstanceMap = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// instance of the pixels not covered by a labeled sprite
+pub const BACKGROUND_INSTANCE: u16 = 0;
+
+/// sprite pixels under this alpha are blended in the background and do not belong to the sprite
+const MASK_ALPHA_THRESHOLD: u8 = 128;
+
+/// maximum distance, in pixels, between an instance contour and its simplified polygon
+const POLYGON_EPSILON: f64 = 1.0;
+
+pub(crate) struct PaintedSprite {
+    /// (x_min, y_min, x_max, y_max) extent of the painted pixels, None if none are in the map
+    pub extent: Option<(u32, u32, u32, u32)>,
+    /// opaque pixels of the sprite, including the ones out of the map
+    pub opaque_pixels: u32,
+}
+
+/// marks the opaque pixels of a sprite overlaid at (x, y) as belonging to the given instance
+pub(crate) fn paint_sprite(
+    instances: &mut InstanceMap,
+    sprite: &RgbaImage,
+    x: i64,
+    y: i64,
+    instance: u16,
+) -> PaintedSprite {
+    let mut extent: Option<(u32, u32, u32, u32)> = None;
+    let mut opaque_pixels = 0;
+
+    for (sprite_x, sprite_y, Rgba([.., alpha])) in sprite.enumerate_pixels() {
+        if *alpha < MASK_ALPHA_THRESHOLD {
+            continue;
+        }
+        opaque_pixels += 1;
+
+        let pixel_x = x + sprite_x as i64;
+        let pixel_y = y + sprite_y as i64;
+
+        if pixel_x < 0
+            || pixel_y < 0
+            || pixel_x >= instances.width() as i64
+            || pixel_y >= instances.height() as i64
+        {
+            continue;
+        }
+
+        let (pixel_x, pixel_y) = (pixel_x as u32, pixel_y as u32);
+        instances.put_pixel(pixel_x, pixel_y, Luma([instance]));
+
+        extent = Some(match extent {
+            None => (pixel_x, pixel_y, pixel_x, pixel_y),
+            Some((x_min, y_min, x_max, y_max)) => (
+                x_min.min(pixel_x),
+                y_min.min(pixel_y),
+                x_max.max(pixel_x),
+                y_max.max(pixel_y),
+            ),
+        });
+    }
+
+    PaintedSprite {
+        extent,
+        opaque_pixels,
+    }
+}
+
+/// Sets the visibility of the labels once every sprite is drawn.
+/// `sprite_pixels` holds the number of opaque pixels of the sprite of each label
+pub(crate) fn compute_visibility(labels: &mut [Label], sprite_pixels: &[u32], instances: &InstanceMap) {
+    let max_instance = labels.iter().map(|label| label.instance).max().unwrap_or(0);
+    let mut visible_pixels = vec![0u32; max_instance as usize + 1];
+
+    for pixel in instances.pixels() {
+        if let Some(count) = visible_pixels.get_mut(pixel.0[0] as usize) {
+            *count += 1;
+        }
+    }
+
+    for (label, sprite_pixels) in labels.iter_mut().zip(sprite_pixels) {
+        label.visibility = if *sprite_pixels == 0 {
+            0.0
+        } else {
+            visible_pixels[label.instance as usize] as f32 / *sprite_pixels as f32
+        };
+    }
+}
+
+/// Outline of the visible part of an instance, as pixel coordinates.
+/// `area` is the (x_min, y_min, x_max, y_max) region of the map the instance is searched in.
+/// Only the largest part is kept when the instance is split by an occluding sprite,
+/// returns None if the instance is not visible
+pub fn instance_polygon(
+    instances: &InstanceMap,
+    instance: u16,
+    area: (u32, u32, u32, u32),
+) -> Option<Vec<(u32, u32)>> {
+    let (x_min, y_min, x_max, y_max) = area;
+    let x_max = x_max.min(instances.width().saturating_sub(1));
+    let y_max = y_max.min(instances.height().saturating_sub(1));
+
+    if x_min > x_max || y_min > y_max {
+        return None;
+    }
+
+    let width = x_max - x_min + 1;
+    let height = y_max - y_min + 1;
+
+    // one pixel of padding so that contours touching the area borders are closed
+    let mask = image::GrayImage::from_fn(width + 2, height + 2, |x, y| {
+        let inside = x > 0 && y > 0 && x <= width && y <= height;
+        if inside && instances.get_pixel(x_min + x - 1, y_min + y - 1).0[0] == instance {
+            Luma([255])
+        } else {
+            Luma([0])
+        }
+    });
+
+    let contour = find_contours::<i32>(&mask)
+        .into_iter()
+        .filter(|contour| contour.border_type == BorderType::Outer)
+        .max_by(|a, b| contour_area(&a.points).total_cmp(&contour_area(&b.points)))?;
+
+    let polygon = approximate_polygon_dp(&contour.points, POLYGON_EPSILON, true);
+    if polygon.len() < 3 {
+        return None;
+    }
+
+    Some(
+        polygon
+            .into_iter()
+            .map(|point| {
+                (
+                    point.x as u32 + x_min - 1,
+                    point.y as u32 + y_min - 1,
+                )
+            })
+            .collect(),
+    )
+}
@@ -1,16 +1,20 @@
-use image::GenericImageView;
+use std::collections::{HashMap, HashSet};
+
 use crate::cell::Cell;
-use crate::label::Bounds;
-use crate::render::{get_plate_pixel_position, Image, resize_image_by_width};
-use crate::village::Village;
-use crate::wall::{Wall, WallConnectionType};
+use crate::label::{Bounds, SpriteBounds};
+use crate::render::{cache, get_plate_pixel_position, Image};
+use crate::render::instance::{InstanceMap, paint_sprite};
+use crate::village::{ComponentType, Village};
+use crate::wall::Wall;
 
 pub(super) fn render_wall(
     scenery_image: &mut Image,
+    instances: &mut InstanceMap,
+    instance: u16,
     village: &Village,
     cell: Cell,
     wall: &Wall,
-) -> Bounds {
+) -> SpriteBounds {
     let wall_state = village.get_wall_connection_type(cell).expect("given cell does not hosts any wall");
     let state_name = wall_state.name();
 
@@ -18,8 +22,8 @@ pub(super) fn render_wall(
 
     let wall_image_file = format!("assets/walls/level_{}/{state_name}.png", wall.level);
 
-    let wall_image = image::open(wall_image_file).unwrap().to_rgba8();
-    let wall_image = resize_image_by_width(&wall_image, (wall_image.width() as f32 * wall_state.size_ratio()) as u32);
+    let width = (cache::image(&wall_image_file).width() as f32 * wall_state.size_ratio()) as u32;
+    let wall_image = cache::image_by_width(&wall_image_file, width);
 
     let (x, y) = get_plate_pixel_position(cell.to_pos(), scenery);
     let height_radius = wall_image.height() as i64;
@@ -28,15 +32,61 @@ pub(super) fn render_wall(
     let x = x + (height_radius as f32 * wall_state.width_shift_ratio()) as i64;
     let y = y - (width_radius as f32 * wall_state.height_shift_ratio()) as i64;
 
-    image::imageops::overlay(scenery_image, &wall_image, x, y);
+    image::imageops::overlay(scenery_image, wall_image.as_ref(), x, y);
+    let painted = paint_sprite(instances, &wall_image, x, y, instance);
+
+    let x_center_pixels = x as f32 + wall_image.width() as f32 / 2.0;
+    let y_center_pixels = y as f32 + wall_image.height() as f32 / 2.0;
+
+    SpriteBounds {
+        sprite: Bounds {
+            x_center: x_center_pixels / scenery_image.width() as f32,
+            y_center: y_center_pixels / scenery_image.height() as f32,
+            height: wall_image.height() as f32 / scenery_image.height() as f32,
+            width: wall_image.width() as f32 / scenery_image.width() as f32,
+        },
+        opaque: painted
+            .extent
+            .map(|extent| Bounds::from_extent(extent, scenery_image.width(), scenery_image.height())),
+        opaque_pixels: painted.opaque_pixels,
+    }
+}
+
+/// Groups the walls of the village into segments: maximal straight runs of walls.
+/// Walls at the crossing of two runs belong to the run along the x axis.
+/// Returns the segment of every wall cell
+pub(super) fn wall_segments(village: &Village) -> HashMap<Cell, usize> {
+    let walls = village
+        .iter_components()
+        .filter(|(_, _, component)| matches!(component.kind, ComponentType::Wall(_)))
+        .map(|(_, cell, _)| cell)
+        .collect::<HashSet<_>>();
+
+    let is_wall = |x: i16, y: i16| walls.contains(&Cell::new(x, y));
+    let in_x_run = |cell: Cell| is_wall(cell.x - 1, cell.y) || is_wall(cell.x + 1, cell.y);
+
+    // segments are identified by their first cell and their axis
+    let mut segment_ids: HashMap<(Cell, bool), usize> = HashMap::new();
+    let mut segments = HashMap::new();
 
-    let x_center_pixels = x as f32 + scenery.cell_width() / 2.0;
-    let y_center_pixels = y as f32 + scenery.cell_height() / 2.0;
+    for &cell in &walls {
+        let along_x = in_x_run(cell);
 
-    Bounds {
-        x_center: x_center_pixels / scenery_image.width() as f32,
-        y_center: y_center_pixels / scenery_image.height() as f32,
-        height: wall_image.height() as f32 / scenery_image.height() as f32,
-        width: wall_image.width() as f32 / scenery_image.width() as f32,
+        let mut first = cell;
+        if along_x {
+            while is_wall(first.x - 1, first.y) {
+                first.x -= 1;
+            }
+        } else {
+            while is_wall(first.x, first.y - 1) && !in_x_run(Cell::new(first.x, first.y - 1)) {
+                first.y -= 1;
+            }
+        }
+
+        let next_id = segment_ids.len();
+        let id = *segment_ids.entry((first, along_x)).or_insert(next_id);
+        segments.insert(cell, id);
     }
+
+    segments
 }
@@ -0,0 +1,29 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Random generator used for everything that ends up in a dataset, so that any entry can be regenerated.
+pub type SeededRng = ChaCha8Rng;
+
+/// Derives the seed of one dataset entry from the master seed.
+/// The derivation must stay stable between runs and machines, so std's hasher is not used here.
+pub fn derive_seed(master_seed: u64, stream: &str, id: usize) -> u64 {
+    // FNV-1a over the stream name and the entry id
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in stream.bytes().chain((id as u64).to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    splitmix64(master_seed ^ hash)
+}
+
+pub fn entry_rng(master_seed: u64, stream: &str, id: usize) -> SeededRng {
+    SeededRng::seed_from_u64(derive_seed(master_seed, stream, id))
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
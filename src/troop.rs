@@ -1,37 +1,128 @@
+use std::fmt::{Display, Formatter};
+
 use enum_assoc::Assoc;
 use image::Rgb;
+use serde::{Deserialize, Serialize};
 
 use crate::position::Pos;
 
-#[derive(Clone)]
+const BARBARIAN_HIT_POINTS: [f32; 12] = [45.0, 54.0, 65.0, 85.0, 105.0, 125.0, 160.0, 205.0, 230.0, 250.0, 275.0, 300.0];
+const ARCHER_HIT_POINTS: [f32; 13] = [20.0, 23.0, 28.0, 33.0, 40.0, 44.0, 48.0, 52.0, 56.0, 60.0, 64.0, 68.0, 72.0];
+const GIANT_HIT_POINTS: [f32; 13] = [
+    300.0, 360.0, 450.0, 600.0, 800.0, 1100.0, 1300.0, 1500.0, 1850.0, 2000.0, 2200.0, 2400.0, 2600.0,
+];
+const GOBLIN_HIT_POINTS: [f32; 9] = [25.0, 30.0, 36.0, 50.0, 65.0, 80.0, 105.0, 126.0, 146.0];
+const WALL_BREAKER_HIT_POINTS: [f32; 12] = [20.0, 24.0, 29.0, 35.0, 53.0, 72.0, 82.0, 92.0, 112.0, 130.0, 140.0, 150.0];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Troop {
     pub tpe: TroopType,
+    /// troops of the plans without a level are level 1
+    #[serde(default = "first_level")]
+    pub level: u8,
     pub pos: Pos,
 }
 
-#[derive(Assoc, Clone)]
-#[func(pub fn prefer_defenses(& self) -> bool { true })]
+fn first_level() -> u8 {
+    1
+}
+
+/// The level of a troop is not one of the levels of its type
+#[derive(Debug)]
+pub struct InvalidTroopLevel {
+    pub troop: TroopType,
+    pub level: u8,
+}
+
+impl Display for InvalidTroopLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} troops have levels 1 to {}, got level {}",
+            self.troop,
+            self.troop.hit_points_per_level().len(),
+            self.level
+        )
+    }
+}
+
+impl Troop {
+    pub fn check_level(&self) -> Result<(), InvalidTroopLevel> {
+        if (1..=self.tpe.hit_points_per_level().len()).contains(&(self.level as usize)) {
+            return Ok(());
+        }
+
+        Err(InvalidTroopLevel {
+            troop: self.tpe.clone(),
+            level: self.level,
+        })
+    }
+
+    /// panics if the level of the troop is invalid, see `check_level`
+    pub fn hit_points(&self) -> f32 {
+        if let Err(err) = self.check_level() {
+            panic!("{err}")
+        }
+        self.tpe.hit_points_per_level()[self.level as usize - 1]
+    }
+}
+
+/// Components a troop goes for first
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FavouriteTarget {
+    /// buildings that fire at the troops
+    Defenses,
+    /// mines, collectors, drills and storages
+    Resources,
+    /// the closest wall
+    Walls,
+    /// the closest building, walls excluded
+    Any,
+}
+
+#[derive(Assoc, Clone, Debug, Serialize, Deserialize)]
+#[func(pub fn favourite_target(& self) -> FavouriteTarget { FavouriteTarget::Any })]
 #[func(pub fn range(& self) -> f32)]
+#[func(pub fn hit_points_per_level(& self) -> & 'static [f32])]
 #[func(pub fn damage_per_seconds(& self) -> f32)]
 #[func(pub fn walk_speed(& self) -> f32)]
 #[func(pub fn color(& self) -> Rgb < u8 >)]
 pub enum TroopType {
     #[assoc(range = 0.0)]
+    #[assoc(hit_points_per_level = &BARBARIAN_HIT_POINTS)]
     #[assoc(damage_per_seconds = 10.0)]
     #[assoc(walk_speed = 1.0)]
     #[assoc(color = Rgb([255, 0, 0]))]
     Barbarian,
 
     #[assoc(range = 4.0)]
+    #[assoc(hit_points_per_level = &ARCHER_HIT_POINTS)]
     #[assoc(damage_per_seconds = 15.0)]
     #[assoc(walk_speed = 1.5)]
     #[assoc(color = Rgb([0, 255, 0]))]
     Archer,
 
     #[assoc(range = 0.0)]
-    #[assoc(prefer_defenses = true)]
+    #[assoc(favourite_target = FavouriteTarget::Defenses)]
+    #[assoc(hit_points_per_level = &GIANT_HIT_POINTS)]
     #[assoc(damage_per_seconds = 30.0)]
     #[assoc(walk_speed = 0.5)]
     #[assoc(color = Rgb([0, 0, 255]))]
     Giant,
+
+    #[assoc(range = 0.0)]
+    #[assoc(favourite_target = FavouriteTarget::Resources)]
+    #[assoc(hit_points_per_level = &GOBLIN_HIT_POINTS)]
+    #[assoc(damage_per_seconds = 11.0)]
+    #[assoc(walk_speed = 2.0)]
+    #[assoc(color = Rgb([255, 255, 0]))]
+    Goblin,
+
+    #[assoc(range = 0.0)]
+    #[assoc(favourite_target = FavouriteTarget::Walls)]
+    #[assoc(hit_points_per_level = &WALL_BREAKER_HIT_POINTS)]
+    #[assoc(damage_per_seconds = 40.0)]
+    #[assoc(walk_speed = 2.0)]
+    #[assoc(color = Rgb([160, 160, 160]))]
+    WallBreaker,
 }
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use crate::buidling::BuildingType;
+use crate::village::{ComponentId, ComponentType, Village};
+
+pub const MAX_TOWN_HALL_LEVEL: u8 = 16;
+/// first level of every building and wall
+const MIN_LEVEL: u8 = 1;
+
+/// Per town hall level limits of a building type, index 0 stands for town hall 1.
+struct BuildingRules {
+    max_counts: [u8; MAX_TOWN_HALL_LEVEL as usize],
+    max_levels: [u8; MAX_TOWN_HALL_LEVEL as usize],
+}
+
+const fn rules(max_counts: [u8; 16], max_levels: [u8; 16]) -> BuildingRules {
+    BuildingRules {
+        max_counts,
+        max_levels,
+    }
+}
+
+const COLLECTOR_RULES: BuildingRules = rules(
+    [1, 2, 3, 4, 5, 6, 6, 6, 6, 7, 7, 7, 7, 7, 7, 7],
+    [2, 4, 6, 8, 10, 10, 11, 12, 12, 13, 14, 15, 15, 16, 16, 16],
+);
+
+const STORAGE_RULES: BuildingRules = rules(
+    [1, 1, 2, 2, 2, 2, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4],
+    [1, 3, 6, 8, 9, 10, 11, 11, 11, 11, 12, 13, 14, 15, 16, 17],
+);
+
+const WALL_MAX_COUNTS: [u16; 16] = [0, 25, 50, 75, 100, 125, 175, 225, 250, 275, 300, 300, 300, 325, 325, 325];
+const WALL_MAX_LEVELS: [u8; 16] = [0, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15, 16, 17];
+
+fn building_rules(building_type: &BuildingType) -> BuildingRules {
+    match building_type {
+        BuildingType::AirBomb => rules(
+            [0, 0, 0, 0, 2, 2, 2, 4, 4, 5, 6, 6, 6, 7, 8, 8],
+            [0, 0, 0, 0, 2, 3, 4, 4, 5, 6, 8, 9, 10, 11, 11, 12],
+        ),
+        BuildingType::ArcherTower(_) => rules(
+            [0, 1, 1, 2, 3, 3, 4, 5, 6, 7, 8, 8, 8, 8, 8, 8],
+            [0, 2, 3, 4, 6, 7, 8, 10, 11, 13, 15, 17, 19, 20, 21, 21],
+        ),
+        BuildingType::ArmyCamp => rules(
+            [1, 1, 2, 2, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4],
+            [1, 2, 3, 4, 5, 6, 6, 6, 7, 8, 9, 10, 11, 11, 12, 12],
+        ),
+        BuildingType::Barracks => rules(
+            [1; 16],
+            [3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 17],
+        ),
+        BuildingType::Blacksmith => rules(
+            [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+        ),
+        BuildingType::BuilderHut => rules(
+            [5; 16],
+            [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 5, 6],
+        ),
+        BuildingType::Cannon(_) => rules(
+            [2, 2, 2, 2, 3, 3, 5, 5, 5, 6, 7, 7, 7, 7, 7, 7],
+            [2, 3, 4, 5, 6, 7, 8, 10, 11, 13, 15, 17, 19, 20, 21, 21],
+        ),
+        BuildingType::DarkBarracks => rules(
+            [0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 2, 4, 6, 7, 8, 9, 9, 10, 11, 11],
+        ),
+        BuildingType::DarkElixirDrill => rules(
+            [0, 0, 0, 0, 0, 0, 1, 2, 2, 3, 3, 3, 3, 3, 3, 3],
+            [0, 0, 0, 0, 0, 0, 3, 3, 6, 7, 8, 9, 9, 9, 10, 10],
+        ),
+        BuildingType::DarkElixirStorage => rules(
+            [0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 2, 4, 6, 6, 6, 7, 8, 9, 10, 11],
+        ),
+        BuildingType::DarkSpellFactory(_) => rules(
+            [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 2, 4, 5, 5, 5, 5, 5, 6, 6],
+        ),
+        BuildingType::EagleArtillery(_) => rules(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3, 4, 5, 6, 7],
+        ),
+        BuildingType::ElixirCollector(_) => COLLECTOR_RULES,
+        BuildingType::ElixirStorage(_) => STORAGE_RULES,
+        BuildingType::GiantBomb(_) => rules(
+            [0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 5, 5, 6, 6, 7, 7],
+            [0, 0, 0, 0, 0, 2, 2, 3, 3, 4, 5, 5, 6, 7, 8, 9],
+        ),
+        BuildingType::GoldMine => COLLECTOR_RULES,
+        BuildingType::GoldStorage(_) => STORAGE_RULES,
+        BuildingType::HiddenTesla => rules(
+            [0, 0, 0, 0, 0, 0, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4],
+            [0, 0, 0, 0, 0, 0, 3, 6, 8, 8, 9, 10, 12, 13, 14, 15],
+        ),
+        BuildingType::InfernoTower(_) => rules(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 3, 3, 3, 3],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 5, 6, 7, 8, 9, 10],
+        ),
+        BuildingType::Laboratory => rules(
+            [0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14],
+        ),
+        BuildingType::Mortar(_) => rules(
+            [0, 0, 1, 1, 1, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4],
+            [0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 12, 13, 14, 15, 16],
+        ),
+        BuildingType::PetHouse => rules(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 8, 10],
+        ),
+        BuildingType::Scattershot(_) => rules(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 2],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3, 4, 5],
+        ),
+        BuildingType::SeekingAirMine => rules(
+            [0, 0, 0, 0, 0, 0, 1, 2, 4, 5, 5, 6, 7, 7, 8, 8],
+            [0, 0, 0, 0, 0, 0, 1, 1, 2, 3, 3, 3, 4, 5, 5, 6],
+        ),
+        BuildingType::SpellFactory(_) => rules(
+            [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 1, 2, 3, 3, 4, 5, 6, 6, 6, 7, 7, 7],
+        ),
+        BuildingType::TownHall => rules(
+            [1; 16],
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16],
+        ),
+        BuildingType::WizardTower => rules(
+            [0, 0, 0, 0, 1, 2, 2, 3, 4, 4, 5, 5, 5, 5, 5, 5],
+            [0, 0, 0, 0, 2, 3, 4, 6, 7, 9, 10, 11, 13, 14, 15, 16],
+        ),
+        BuildingType::Workshop => rules(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 5, 6, 7, 7],
+        ),
+    }
+}
+
+fn town_hall_idx(town_hall_level: u8) -> usize {
+    (town_hall_level.clamp(1, MAX_TOWN_HALL_LEVEL) - 1) as usize
+}
+
+pub fn max_building_count(building_type: &BuildingType, town_hall_level: u8) -> u8 {
+    building_rules(building_type).max_counts[town_hall_idx(town_hall_level)]
+}
+
+pub fn max_building_level(building_type: &BuildingType, town_hall_level: u8) -> u8 {
+    building_rules(building_type).max_levels[town_hall_idx(town_hall_level)]
+}
+
+pub fn max_wall_count(town_hall_level: u8) -> u16 {
+    WALL_MAX_COUNTS[town_hall_idx(town_hall_level)]
+}
+
+pub fn max_wall_level(town_hall_level: u8) -> u8 {
+    WALL_MAX_LEVELS[town_hall_idx(town_hall_level)]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    MissingTownHall,
+    TooManyTownHalls {
+        count: usize,
+    },
+    InvalidTownHallLevel {
+        level: u8,
+    },
+    TooManyBuildings {
+        building: &'static str,
+        count: usize,
+        max: u8,
+    },
+    BuildingLevelTooHigh {
+        component: ComponentId,
+        building: &'static str,
+        level: u8,
+        max: u8,
+    },
+    BuildingLevelTooLow {
+        component: ComponentId,
+        building: &'static str,
+        level: u8,
+        min: u8,
+    },
+    TooManyWalls {
+        count: usize,
+        max: u16,
+    },
+    WallLevelTooHigh {
+        component: ComponentId,
+        level: u8,
+        max: u8,
+    },
+    WallLevelTooLow {
+        component: ComponentId,
+        level: u8,
+        min: u8,
+    },
+}
+
+/// Checks the village against the rules of its town hall level.
+/// An empty list means that the village could exist in the game.
+pub fn validate_village(village: &Village) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let town_hall_levels = village
+        .iter_components()
+        .filter_map(|(_, _, c)| match &c.kind {
+            ComponentType::Building(b) if matches!(b.building_type, BuildingType::TownHall) => Some(b.level),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // the rules of the highest town hall are used if there are more than one
+    let Some(town_hall_level) = town_hall_levels.iter().max().copied() else {
+        violations.push(Violation::MissingTownHall);
+        return violations;
+    };
+
+    if town_hall_levels.len() > 1 {
+        violations.push(Violation::TooManyTownHalls {
+            count: town_hall_levels.len(),
+        });
+    }
+
+    if !(1..=MAX_TOWN_HALL_LEVEL).contains(&town_hall_level) {
+        violations.push(Violation::InvalidTownHallLevel {
+            level: town_hall_level,
+        });
+        return violations;
+    }
+
+    let mut building_counts: HashMap<&'static str, (usize, u8)> = HashMap::new();
+    let mut wall_count = 0;
+
+    for (id, _, component) in village.iter_components() {
+        match &component.kind {
+            ComponentType::Building(building) => {
+                let building_type = &building.building_type;
+                let max_level = max_building_level(building_type, town_hall_level);
+
+                if building.level > max_level {
+                    violations.push(Violation::BuildingLevelTooHigh {
+                        component: id,
+                        building: building_type.name(),
+                        level: building.level,
+                        max: max_level,
+                    });
+                } else if building.level < MIN_LEVEL {
+                    violations.push(Violation::BuildingLevelTooLow {
+                        component: id,
+                        building: building_type.name(),
+                        level: building.level,
+                        min: MIN_LEVEL,
+                    });
+                }
+
+                if !matches!(building_type, BuildingType::TownHall) {
+                    building_counts
+                        .entry(building_type.name())
+                        .or_insert((0, max_building_count(building_type, town_hall_level)))
+                        .0 += 1;
+                }
+            }
+            ComponentType::Wall(wall) => {
+                let max_level = max_wall_level(town_hall_level);
+
+                if wall.level > max_level {
+                    violations.push(Violation::WallLevelTooHigh {
+                        component: id,
+                        level: wall.level,
+                        max: max_level,
+                    });
+                } else if wall.level < MIN_LEVEL {
+                    violations.push(Violation::WallLevelTooLow {
+                        component: id,
+                        level: wall.level,
+                        min: MIN_LEVEL,
+                    });
+                }
+
+                wall_count += 1;
+            }
+        }
+    }
+
+    let mut building_counts = building_counts.into_iter().collect::<Vec<_>>();
+    building_counts.sort_by_key(|(name, _)| *name);
+
+    for (building, (count, max)) in building_counts {
+        if count > max as usize {
+            violations.push(Violation::TooManyBuildings {
+                building,
+                count,
+                max,
+            });
+        }
+    }
+
+    let max_walls = max_wall_count(town_hall_level);
+    if wall_count > max_walls as usize {
+        violations.push(Violation::TooManyWalls {
+            count: wall_count,
+            max: max_walls,
+        });
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buidling::{Building, BuildingCharacteristics, BuildingType};
+    use crate::cell::Cell;
+    use crate::village::{Component, ComponentId, ComponentType, Village};
+    use crate::wall::Wall;
+
+    use super::{validate_village, Violation};
+
+    fn building(building_type: BuildingType, level: u8) -> Component {
+        Component {
+            life_points: None,
+            kind: ComponentType::Building(Building {
+                building_type,
+                level,
+                characteristics: BuildingCharacteristics::Passive,
+            }),
+        }
+    }
+
+    fn wall(level: u8) -> Component {
+        Component {
+            life_points: None,
+            kind: ComponentType::Wall(Wall { level }),
+        }
+    }
+
+    /// village of the components, spaced so that they never collide
+    fn village_of(components: Vec<Component>) -> (Village, Vec<ComponentId>) {
+        let mut village = Village::default();
+        let ids = components
+            .into_iter()
+            .enumerate()
+            .map(|(i, component)| {
+                let cell = Cell::new(1 + 5 * (i % 8) as i16, 1 + 5 * (i / 8) as i16);
+                village.add_component(cell, component).unwrap()
+            })
+            .collect();
+        (village, ids)
+    }
+
+    #[test]
+    fn legal_villages_have_no_violation() {
+        let (village, _) = village_of(vec![
+            building(BuildingType::TownHall, 2),
+            building(BuildingType::Cannon(Default::default()), 3),
+            wall(2),
+        ]);
+
+        assert_eq!(validate_village(&village), vec![]);
+    }
+
+    #[test]
+    fn villages_need_a_single_town_hall() {
+        let (village, _) = village_of(vec![building(BuildingType::Cannon(Default::default()), 1)]);
+        assert_eq!(validate_village(&village), vec![Violation::MissingTownHall]);
+
+        let (village, _) = village_of(vec![
+            building(BuildingType::TownHall, 2),
+            building(BuildingType::TownHall, 2),
+        ]);
+        assert_eq!(validate_village(&village), vec![Violation::TooManyTownHalls { count: 2 }]);
+    }
+
+    #[test]
+    fn town_hall_levels_must_exist() {
+        for level in [0, 17] {
+            let (village, _) = village_of(vec![building(BuildingType::TownHall, level)]);
+            assert_eq!(validate_village(&village), vec![Violation::InvalidTownHallLevel { level }]);
+        }
+    }
+
+    #[test]
+    fn building_counts_are_limited_by_the_town_hall() {
+        let cannon = || building(BuildingType::Cannon(Default::default()), 1);
+        let (village, _) = village_of(vec![building(BuildingType::TownHall, 1), cannon(), cannon(), cannon()]);
+
+        assert_eq!(
+            validate_village(&village),
+            vec![Violation::TooManyBuildings {
+                building: "Cannon",
+                count: 3,
+                max: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn building_levels_are_limited_by_the_town_hall() {
+        let (village, ids) = village_of(vec![
+            building(BuildingType::TownHall, 2),
+            building(BuildingType::Cannon(Default::default()), 4),
+            building(BuildingType::Cannon(Default::default()), 0),
+        ]);
+
+        assert_eq!(
+            validate_village(&village),
+            vec![
+                Violation::BuildingLevelTooHigh {
+                    component: ids[1],
+                    building: "Cannon",
+                    level: 4,
+                    max: 3,
+                },
+                Violation::BuildingLevelTooLow {
+                    component: ids[2],
+                    building: "Cannon",
+                    level: 0,
+                    min: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn wall_counts_are_limited_by_the_town_hall() {
+        let mut components = vec![building(BuildingType::TownHall, 2)];
+        components.extend((0..26).map(|_| wall(1)));
+        let (village, _) = village_of(components);
+
+        assert_eq!(validate_village(&village), vec![Violation::TooManyWalls { count: 26, max: 25 }]);
+    }
+
+    #[test]
+    fn wall_levels_are_limited_by_the_town_hall() {
+        let (village, ids) = village_of(vec![building(BuildingType::TownHall, 2), wall(3), wall(0)]);
+
+        assert_eq!(
+            validate_village(&village),
+            vec![
+                Violation::WallLevelTooHigh {
+                    component: ids[1],
+                    level: 3,
+                    max: 2,
+                },
+                Violation::WallLevelTooLow {
+                    component: ids[2],
+                    level: 0,
+                    min: 1,
+                },
+            ]
+        );
+    }
+}
@@ -1,24 +1,37 @@
-use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
 
 use crate::buidling::{Building, BuildingCharacteristics, PlotSize};
 use crate::cell::Cell;
+use crate::position::Pos;
 use crate::scenery::Scenery;
 use crate::wall::{Wall, WallConnectionType};
 
 #[derive(Clone)]
 pub struct Village {
-    grid: Vec<Option<Box<Component>>>,
+    // for each cell, the component whose footprint covers it
+    grid: Vec<Option<ComponentId>>,
+    components: BTreeMap<ComponentId, PlacedComponent>,
+    next_component_id: usize,
     state: State,
     scenery: Scenery,
 }
 
 #[derive(Clone)]
+struct PlacedComponent {
+    origin: Cell,
+    component: Component,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum ComponentType {
     Building(Building),
     Wall(Wall),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Component {
     pub life_points: Option<f32>,
     pub kind: ComponentType,
@@ -35,6 +48,49 @@ impl Component {
             ComponentType::Wall(_) => PlotSize::X1Invisible
         }
     }
+
+    /// list all the cells covered by the component's plot, if placed at given origin
+    pub fn footprint(&self, origin: Cell) -> Vec<Cell> {
+        let diameter = self.get_plot_size().cell_diameter() as i16;
+
+        let mut cells = Vec::new();
+
+        for x in 0..diameter {
+            for y in 0..diameter {
+                cells.push(Cell::new(origin.x + x, origin.y + y));
+            }
+        }
+
+        cells
+    }
+
+    /// the same component at another level, with the life points and characteristics of that level
+    pub fn with_level(&self, level: u8) -> Component {
+        match &self.kind {
+            ComponentType::Building(building) => Component {
+                life_points: building.building_type.life_points(level),
+                kind: ComponentType::Building(Building {
+                    building_type: building.building_type.clone(),
+                    level,
+                    characteristics: building.building_type.characteristics(level),
+                }),
+            },
+            ComponentType::Wall(_) => {
+                let wall = Wall { level };
+                Component {
+                    life_points: Some(wall.life_points()),
+                    kind: ComponentType::Wall(wall),
+                }
+            }
+        }
+    }
+
+    /// center of the component's plot, if placed at given origin
+    pub fn center(&self, origin: Cell) -> Pos {
+        let radius = (self.get_plot_size().cell_diameter() as f32 - 1.0) / 2.0;
+
+        Pos::new(origin.x as f32 + radius, origin.y as f32 + radius)
+    }
 }
 
 #[derive(Default, Clone)]
@@ -47,9 +103,22 @@ pub type VillageOperationResult<R> = Result<R, VillageOperationError>;
 #[derive(Debug)]
 pub enum VillageOperationError {
     ComponentCollides,
+    OutOfBounds,
+    ComponentNotFound(ComponentId),
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+impl Display for VillageOperationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VillageOperationError::ComponentCollides => write!(f, "the component collides with another one"),
+            VillageOperationError::OutOfBounds => write!(f, "the component is out of the village"),
+            VillageOperationError::ComponentNotFound(id) => write!(f, "component {} not found", id.0),
+        }
+    }
+}
+
+/// identifies a component of a village, stays the same as long as the component is not removed
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Ord, PartialOrd)]
 pub struct ComponentId(usize);
 
 impl Default for Village {
@@ -58,6 +127,8 @@ impl Default for Village {
         let grid_size = (scenery.params().plate_width_cells as usize + 1) * (scenery.params().plate_height_cells as usize + 1);
         Self {
             grid: vec![None; grid_size],
+            components: BTreeMap::new(),
+            next_component_id: 0,
             state: Default::default(),
             scenery,
         }
@@ -73,34 +144,97 @@ impl Village {
         &self.scenery
     }
 
-    fn can_plot_fit(&self, at: Cell, plot_size: PlotSize) -> bool {
-        let plot_diameter = plot_size.cell_diameter();
+    fn is_cell_on_plate(&self, cell: Cell) -> bool {
+        cell.x >= 0
+            && cell.y >= 0
+            && cell.x < self.scenery.params().plate_width_cells as i16
+            && cell.y < self.scenery.params().plate_height_cells as i16
+    }
 
-        for x in 0..plot_diameter {
-            for y in 0..plot_diameter {
-                let cell = Cell::new(at.x + x as i16, at.y + y as i16);
-                if self.get_component_at(cell).is_some() {
-                    return false;
-                }
+    fn check_plot_fit(&self, footprint: &[Cell]) -> VillageOperationResult<()> {
+        for cell in footprint {
+            if !self.is_cell_on_plate(*cell) {
+                return Err(VillageOperationError::OutOfBounds);
+            }
+            if self.get_component_at(*cell).is_some() {
+                return Err(VillageOperationError::ComponentCollides);
             }
         }
 
-        true
+        Ok(())
     }
 
     pub fn add_component(&mut self, cell: Cell, component: Component) -> VillageOperationResult<ComponentId> {
-        if !self.can_plot_fit(cell, component.get_plot_size()) {
-            return Err(VillageOperationError::ComponentCollides);
+        self.check_plot_fit(&component.footprint(cell))?;
+
+        let id = ComponentId(self.next_component_id);
+        self.next_component_id += 1;
+
+        self.place_component(id, cell, component);
+
+        Ok(id)
+    }
+
+    pub fn remove_component(&mut self, id: ComponentId) -> VillageOperationResult<Component> {
+        self.unplace_component(id).map(|p| p.component)
+    }
+
+    /// moves the component to the given cell, the component keeps its id
+    pub fn move_component(&mut self, id: ComponentId, to: Cell) -> VillageOperationResult<()> {
+        let placed = self.unplace_component(id)?;
+
+        if let Err(err) = self.check_plot_fit(&placed.component.footprint(to)) {
+            self.place_component(id, placed.origin, placed.component);
+            return Err(err);
+        }
+
+        self.place_component(id, to, placed.component);
+        Ok(())
+    }
+
+    /// replaces the component at the same origin cell, returns the replaced component.
+    /// The new component inherits the id of the replaced one
+    pub fn replace_component(&mut self, id: ComponentId, component: Component) -> VillageOperationResult<Component> {
+        let placed = self.unplace_component(id)?;
+
+        if let Err(err) = self.check_plot_fit(&component.footprint(placed.origin)) {
+            self.place_component(id, placed.origin, placed.component);
+            return Err(err);
         }
 
-        if is_defensive_building(&component) {
+        self.place_component(id, placed.origin, component);
+        Ok(placed.component)
+    }
+
+    fn place_component(&mut self, id: ComponentId, origin: Cell, component: Component) {
+        if is_defensive_building(&component) && !component.is_destroyed() {
             self.state.remaining_defenses += 1;
         }
 
-        let idx = self.get_cell_idx(cell);
-        self.grid[idx] = Some(Box::new(component));
+        for cell in component.footprint(origin) {
+            let idx = self.get_cell_idx(cell);
+            self.grid[idx] = Some(id);
+        }
+
+        self.components.insert(id, PlacedComponent { origin, component });
+    }
+
+    fn unplace_component(&mut self, id: ComponentId) -> VillageOperationResult<PlacedComponent> {
+        let placed = self
+            .components
+            .remove(&id)
+            .ok_or(VillageOperationError::ComponentNotFound(id))?;
+
+        for cell in placed.component.footprint(placed.origin) {
+            let idx = self.get_cell_idx(cell);
+            self.grid[idx] = None;
+        }
+
+        if is_defensive_building(&placed.component) && !placed.component.is_destroyed() {
+            self.state.remaining_defenses -= 1;
+        }
 
-        Ok(ComponentId(idx))
+        Ok(placed)
     }
 
     pub fn get_wall_connection_type(&self, cell: Cell) -> Option<WallConnectionType> {
@@ -149,20 +283,34 @@ impl Village {
     }
 
     fn get_cell_idx(&self, cell: Cell) -> usize {
-        cell.x as usize * self.scenery.params().plate_width_cells as usize + cell.y as usize
+        cell.x as usize * (self.scenery.params().plate_height_cells as usize + 1) + cell.y as usize
     }
 
-    fn get_component_at(&self, cell: Cell) -> Option<(ComponentId, &Component)> {
-        let idx = self.get_cell_idx(cell);
-        self.get_component(ComponentId(idx)).map(|v| (ComponentId(idx), v))
+    /// returns the component whose footprint covers the given cell
+    pub fn get_component_at(&self, cell: Cell) -> Option<(ComponentId, &Component)> {
+        if cell.x < 0
+            || cell.y < 0
+            || cell.x > self.scenery.params().plate_width_cells as i16
+            || cell.y > self.scenery.params().plate_height_cells as i16
+        {
+            return None;
+        }
+
+        let id = self.grid[self.get_cell_idx(cell)]?;
+        self.get_component(id).map(|c| (id, c))
     }
 
-    fn get_component(&self, id: ComponentId) -> Option<&Component> {
-        self.grid[id.0].as_ref().map(|b| b.as_ref())
+    pub fn get_component(&self, id: ComponentId) -> Option<&Component> {
+        self.components.get(&id).map(|p| &p.component)
+    }
+
+    /// cells covered by the component's plot
+    pub fn get_component_footprint(&self, id: ComponentId) -> Option<Vec<Cell>> {
+        self.components.get(&id).map(|p| p.component.footprint(p.origin))
     }
 
     fn get_component_mut(&mut self, id: ComponentId) -> Option<&mut Component> {
-        self.grid[id.0].as_mut().map(|b| b.as_mut())
+        self.components.get_mut(&id).map(|p| &mut p.component)
     }
 
     pub fn is_component_destroyed(&self, comp_id: ComponentId) -> bool {
@@ -175,20 +323,13 @@ impl Village {
     }
 
     pub fn is_cell_blocked(&self, cell: Cell) -> bool {
-        self.get_component_at(cell).is_some_and(|(_, c)| c.is_destroyed())
+        self.get_component_at(cell).is_some_and(|(_, c)| !c.is_destroyed())
     }
 
     pub fn iter_components(&self) -> impl Iterator<Item=(ComponentId, Cell, &Component)> {
-        self.grid
+        self.components
             .iter()
-            .enumerate()
-            .filter_map(|(idx, b)| b.as_ref().map(|bx| (idx, bx.as_ref())))
-            .map(|(idx, b)| {
-                (ComponentId(idx), Cell {
-                    x: (idx / self.scenery.params().plate_width_cells as usize) as i16,
-                    y: (idx % self.scenery.params().plate_width_cells as usize) as i16,
-                }, b)
-            })
+            .map(|(id, p)| (*id, p.origin, &p.component))
     }
 }
 
@@ -232,3 +373,97 @@ const fn collides(pos_a: Cell, size_a: PlotSize, pos_b: Cell, size_b: PlotSize)
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::buidling::{Building, BuildingType};
+    use crate::cell::Cell;
+
+    use super::{Component, ComponentId, ComponentType, Village, VillageOperationError};
+
+    fn building(building_type: BuildingType) -> Component {
+        Component {
+            life_points: Some(1000.0),
+            kind: ComponentType::Building(Building {
+                characteristics: building_type.characteristics(5),
+                building_type,
+                level: 5,
+            }),
+        }
+    }
+
+    fn component_at(village: &Village, cell: Cell) -> Option<ComponentId> {
+        village.get_component_at(cell).map(|(id, _)| id)
+    }
+
+    #[test]
+    fn move_keeps_the_id() {
+        let mut village = Village::default();
+        let id = village.add_component(Cell::new(10, 10), building(BuildingType::TownHall)).unwrap();
+
+        village.move_component(id, Cell::new(20, 20)).unwrap();
+
+        assert_eq!(component_at(&village, Cell::new(20, 20)), Some(id));
+        assert_eq!(component_at(&village, Cell::new(23, 23)), Some(id));
+        assert_eq!(component_at(&village, Cell::new(10, 10)), None);
+        assert_eq!(village.iter_components().map(|(id, _, _)| id).collect::<Vec<_>>(), vec![id]);
+    }
+
+    #[test]
+    fn failed_move_rolls_back() {
+        let mut village = Village::default();
+        let id = village.add_component(Cell::new(10, 10), building(BuildingType::TownHall)).unwrap();
+        let other = village.add_component(Cell::new(20, 20), building(BuildingType::Laboratory)).unwrap();
+
+        let collides = village.move_component(id, Cell::new(18, 18));
+        assert!(matches!(collides, Err(VillageOperationError::ComponentCollides)));
+
+        let out_of_bounds = village.move_component(id, Cell::new(42, 42));
+        assert!(matches!(out_of_bounds, Err(VillageOperationError::OutOfBounds)));
+
+        assert_eq!(component_at(&village, Cell::new(10, 10)), Some(id));
+        assert_eq!(component_at(&village, Cell::new(13, 13)), Some(id));
+        assert_eq!(component_at(&village, Cell::new(20, 20)), Some(other));
+        assert_eq!(component_at(&village, Cell::new(18, 18)), None);
+    }
+
+    #[test]
+    fn failed_replace_rolls_back() {
+        let mut village = Village::default();
+        let id = village.add_component(Cell::new(10, 10), building(BuildingType::HiddenTesla)).unwrap();
+        let other = village.add_component(Cell::new(12, 10), building(BuildingType::Laboratory)).unwrap();
+        let edge = village.add_component(Cell::new(43, 43), building(BuildingType::HiddenTesla)).unwrap();
+
+        let collides = village.replace_component(id, building(BuildingType::TownHall));
+        assert!(matches!(collides, Err(VillageOperationError::ComponentCollides)));
+
+        let out_of_bounds = village.replace_component(edge, building(BuildingType::TownHall));
+        assert!(matches!(out_of_bounds, Err(VillageOperationError::OutOfBounds)));
+
+        assert_eq!(component_at(&village, Cell::new(10, 10)), Some(id));
+        assert_eq!(component_at(&village, Cell::new(11, 11)), None);
+        assert_eq!(component_at(&village, Cell::new(12, 10)), Some(other));
+        assert_eq!(component_at(&village, Cell::new(43, 43)), Some(edge));
+        assert_eq!(village.state().remaining_defenses, 2);
+    }
+
+    #[test]
+    fn remove_updates_the_remaining_defenses() {
+        let mut village = Village::default();
+        let cannon = building(BuildingType::Cannon(Default::default()));
+        let cannon = village.add_component(Cell::new(10, 10), cannon).unwrap();
+        let laboratory = village.add_component(Cell::new(20, 20), building(BuildingType::Laboratory)).unwrap();
+        assert_eq!(village.state().remaining_defenses, 1);
+
+        village.remove_component(laboratory).unwrap();
+        assert_eq!(village.state().remaining_defenses, 1);
+
+        village.remove_component(cannon).unwrap();
+        assert_eq!(village.state().remaining_defenses, 0);
+        assert_eq!(component_at(&village, Cell::new(10, 10)), None);
+        assert!(matches!(
+            village.remove_component(cannon),
+            Err(VillageOperationError::ComponentNotFound(_))
+        ));
+    }
+}
@@ -1,11 +1,24 @@
 use enum_assoc::Assoc;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+const WALL_HIT_POINTS: [f32; 17] = [
+    300.0, 500.0, 700.0, 900.0, 1400.0, 2000.0, 2500.0, 3000.0, 3500.0, 4000.0, 5000.0, 7000.0, 9000.0, 11000.0,
+    12500.0, 13500.0, 14500.0,
+];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Wall {
     pub level: u8,
 }
 
-#[derive(Assoc)]
+impl Wall {
+    pub fn life_points(&self) -> f32 {
+        let idx = (self.level.max(1) as usize - 1).min(WALL_HIT_POINTS.len() - 1);
+        WALL_HIT_POINTS[idx]
+    }
+}
+
+#[derive(Assoc, Copy, Clone)]
 #[func(pub fn name(& self) -> & str)]
 #[func(pub fn size_ratio(& self) -> f32)]
 #[func(pub fn width_shift_ratio(& self) -> f32)]
@@ -35,3 +48,12 @@ pub enum WallConnectionType {
     #[assoc(height_shift_ratio = 0.8)]
     RightOnly,
 }
+
+impl WallConnectionType {
+    pub const ALL: [WallConnectionType; 4] = [
+        WallConnectionType::Lonely,
+        WallConnectionType::RightLeft,
+        WallConnectionType::LeftOnly,
+        WallConnectionType::RightOnly,
+    ];
+}